@@ -1,11 +1,144 @@
+use std::path::PathBuf;
+
 use clap::{Parser, ValueEnum};
 
-#[derive(Parser, Debug)]
+use crate::elementary::{Automaton, Start};
+use crate::game_of_life::{Engine, Gradient, Rule, Symmetry, Topology, MAX_STEP_EXPONENT};
+use crate::turmite::Turmite;
+
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Name of the creative to run
     #[arg(short, long, value_enum, default_value_t=RunOptions::InitialShapes)]
     pub name: RunOptions,
+
+    /// Life-like rule in B/S notation, e.g. B36/S23 (HighLife) or B2/S (Seeds), or its name.
+    /// Generations rules add the number of states, e.g. B2/S/C3 (Brian's Brain).
+    /// A final H or T makes hexagonal or triangular cells, e.g. B2/S34H, on the grid engine.
+    /// Larger than Life rules, e.g. R5,C0,M1,S34..58,B34..45,NM (Bosco), also need the grid engine.
+    /// Defaults to the pattern's rule, or B3/S23
+    #[arg(short, long)]
+    pub rule: Option<Rule>,
+
+    /// Pattern file (.rle, .cells or .lif) loaded in the middle of the grid.
+    /// Wireworld loads Golly's Wireworld .rle files
+    #[arg(short, long)]
+    pub pattern: Option<PathBuf>,
+
+    /// Start the Game of Life from a random soup in the middle of the grid
+    #[arg(long, conflicts_with = "pattern")]
+    pub soup: bool,
+
+    /// With --soup or --start random, chance of each cell being alive, from 0 to 1
+    #[arg(long, default_value_t = 0.5, value_parser = parse_density)]
+    pub density: f64,
+
+    /// With --soup, cells per side of the random square
+    #[arg(long, default_value_t = 16)]
+    pub soup_size: u32,
+
+    /// With --soup or --start random, seed of the random cells, to regenerate a soup
+    /// exactly. Random by default
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// With --soup, symmetry of the random cells
+    #[arg(long, value_enum, default_value_t = Symmetry::None)]
+    pub symmetry: Symmetry,
+
+    /// Where the grid is written when pressing `S`, in the format of its extension.
+    /// game_of_life.rle by default, elementary.pbm for 1D automata
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Where the per-generation statistics are written when pressing `E`, as JSON for a
+    /// .json file and CSV otherwise
+    #[arg(long, default_value = "game_of_life_stats.csv")]
+    pub stats: PathBuf,
+
+    /// Session saved with `W` to pick up where it was left, instead of starting a new one
+    #[arg(long)]
+    pub load: Option<PathBuf>,
+
+    /// Where `W` saves the session. Defaults to game_of_life_session.json or
+    /// falling_sand_session.json
+    #[arg(long)]
+    pub session: Option<PathBuf>,
+
+    /// Colours live cells go through as they age, as comma separated #rrggbb colours
+    /// or one of white, heat and ice
+    #[arg(long, default_value = "white")]
+    pub gradient: Gradient,
+
+    /// Generations dead cells stay as fading ghosts of their last colour
+    #[arg(long, default_value_t = 0)]
+    pub trail: u32,
+
+    /// Pixels per cell of the Game of Life and falling sand grids, from 2 to 64
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(2..=64))]
+    pub cell_size: u32,
+
+    /// Columns of the Game of Life and falling sand grids. Without --columns and --rows
+    /// the grid fills the window and follows it when resized or fullscreen
+    #[arg(long)]
+    pub columns: Option<u32>,
+
+    /// Rows of the Game of Life and falling sand grids
+    #[arg(long)]
+    pub rows: Option<u32>,
+
+    /// How the Game of Life grid edges wrap around. Hashlife and sparse are always unbounded
+    #[arg(short, long, value_enum, default_value_t = Topology::Bounded)]
+    pub topology: Topology,
+
+    /// Game of Life simulation engine. Hashlife, bit grid and sparse can be panned by
+    /// dragging with the middle button and zoomed with the mouse wheel
+    #[arg(short, long, value_enum, default_value_t = Engine::Grid)]
+    pub engine: Engine,
+
+    /// With hashlife, advance 2^N generations per frame, N from 0 to 58
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(..=MAX_STEP_EXPONENT as i64))]
+    pub step_exponent: u8,
+
+    /// With the bit grid, cells per side of the board. Defaults to the window grid.
+    /// Turmites get at least 200, 1D automata get this many cells per row
+    #[arg(long)]
+    pub board_size: Option<u32>,
+
+    /// Look for still lifes, oscillators and spaceships in the Game of Life, shown in the
+    /// window and the headless output. Toggled with `D`
+    #[arg(long)]
+    pub detect: bool,
+
+    /// Run the Game of Life without a window, printing a CSV line per generation
+    /// and writing the final state to `--output` and the statistics to `--stats`.
+    /// Turmites print a CSV line per step, 1D automata write their space-time diagram
+    /// to `--output` as .pbm or .png
+    #[arg(long)]
+    pub headless: bool,
+
+    /// With --headless, generations (hashlife steps, turmite steps) to run
+    #[arg(long, default_value_t = 100)]
+    pub generations: u64,
+
+    /// Turmite rule, a turn per colour like RL (Langton's ant), LLRR or RRLLLRLLLRRR,
+    /// or a state table like {{{1,2,0},{0,8,0}}}
+    #[arg(long, default_value = "RL")]
+    pub turmite: Turmite,
+
+    /// Turmites starting side by side in the middle of the grid
+    #[arg(long, default_value_t = 1)]
+    pub ants: usize,
+
+    /// 1D automaton rule, a Wolfram number from 0 to 255 like 30 or 110, or a
+    /// totalistic code for K colours as K:CODE, like 3:777
+    #[arg(long, default_value = "30")]
+    pub wolfram: Automaton,
+
+    /// First row of the 1D automaton
+    #[arg(long, value_enum, default_value_t = Start::Single)]
+    pub start: Start,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -20,6 +153,21 @@ pub enum RunOptions {
     RgbLinearTransition,
     // "KONWAYS_GAME_OF_LIFE"
     GameOfLife,
+    // "WIREWORLD"
+    Wireworld,
+    // "TURMITE"
+    Turmite,
+    // "ELEMENTARY_CA"
+    Elementary,
     // _
     InitialShapes,
 }
+
+// A chance, from 0 to 1, which random soups and rows can draw cells with.
+fn parse_density(s: &str) -> Result<f64, String> {
+    let density: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    match (0.0..=1.0).contains(&density) {
+        true => Ok(density),
+        false => Err(format!("{density} is not between 0 and 1")),
+    }
+}
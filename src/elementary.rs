@@ -0,0 +1,491 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use nannou::prelude::*;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
+
+use crate::cli_options::Args;
+use crate::game_of_life::{Clock, Topology};
+
+// One dimensional cellular automata: a row of cells where each cell's next colour only
+// depends on itself and its two neighbours. Every new row goes below the last one, so
+// the window shows the history as a space-time diagram scrolling down.
+// https://mathworld.wolfram.com/ElementaryCellularAutomaton.html
+// https://mathworld.wolfram.com/TotalisticCellularAutomaton.html
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = WIDTH;
+const BLOCK_SIZE: u32 = 4; // width and height in pixels
+
+// Rows per second while running.
+const DEFAULT_SPEED: f64 = 30.0;
+const MIN_SPEED: f64 = 1.0;
+const MAX_SPEED: f64 = 960.0;
+// Rows a single frame may add, a window full, past which they would scroll out unseen.
+const MAX_STEPS_PER_FRAME: f64 = (HEIGHT / BLOCK_SIZE) as f64;
+
+// Cells per line of a plain PBM, keeping lines within the 70 characters it allows.
+const PBM_CELLS_PER_LINE: usize = 35;
+
+// Command line options, read once the window model is built.
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+pub fn run(args: &Args) {
+    if args.headless {
+        return run_headless(args);
+    }
+    ARGS.set(args.clone()).unwrap();
+    nannou::app(model).update(update).run();
+}
+
+// How a cell's colour follows from its neighbourhood.
+//
+// An elementary rule is a Wolfram number from 0 to 255, e.g. 30 or 110: bit
+// `4 * left + 2 * centre + right` of the number is the cell's next state.
+//
+// A totalistic rule is written `K:CODE`, e.g. 3:777, for K colours from 2 to 7: the
+// next colour is the digit of CODE in base K at the sum of the three colours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Automaton {
+    Elementary(u8),
+    Totalistic { colors: u8, code: u64 },
+}
+
+impl Automaton {
+    fn colors(&self) -> u8 {
+        match self {
+            Automaton::Elementary(_) => 2,
+            Automaton::Totalistic { colors, .. } => *colors,
+        }
+    }
+
+    fn next(&self, left: u8, centre: u8, right: u8) -> u8 {
+        match self {
+            Automaton::Elementary(number) => (number >> (4 * left + 2 * centre + right)) & 1,
+            Automaton::Totalistic { colors, code } => {
+                let sum = (left + centre + right) as u32;
+                (code / (*colors as u64).pow(sum) % *colors as u64) as u8
+            }
+        }
+    }
+}
+
+impl FromStr for Automaton {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((colors, code)) = s.trim().split_once(':') else {
+            return s
+                .trim()
+                .parse::<u8>()
+                .map(Automaton::Elementary)
+                .map_err(|_| format!("invalid rule `{s}`, expected 0 to 255 or K:CODE"));
+        };
+
+        let colors = colors
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|colors| (2..=7).contains(colors))
+            .ok_or_else(|| format!("invalid colours in rule `{s}`, expected 2 to 7"))?;
+        let code = code
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| format!("invalid code in rule `{s}`"))?;
+        // A digit for every sum from 0 to 3 * (colors - 1).
+        let codes = (colors as u64).pow(3 * (colors as u32 - 1) + 1);
+        if code >= codes {
+            return Err(format!(
+                "code in rule `{s}` must be below {codes} for {colors} colours"
+            ));
+        }
+        Ok(Automaton::Totalistic { colors, code })
+    }
+}
+
+impl fmt::Display for Automaton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Automaton::Elementary(number) => write!(f, "{number}"),
+            Automaton::Totalistic { colors, code } => write!(f, "{colors}:{code}"),
+        }
+    }
+}
+
+// What the first row looks like.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Start {
+    // A single cell of colour 1 in the middle.
+    #[default]
+    Single,
+    // Each cell coloured with probability `--density`, in any colour but 0.
+    Random,
+}
+
+fn first_row(width: usize, colors: u8, start: Start, density: f64, seed: u64) -> Vec<u8> {
+    match start {
+        Start::Single => {
+            let mut row = vec![0; width];
+            row[width / 2] = 1;
+            row
+        }
+        Start::Random => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..width)
+                .map(|_| match rng.gen_bool(density) {
+                    true => rng.gen_range(1..colors),
+                    false => 0,
+                })
+                .collect()
+        }
+    }
+}
+
+// The row after `row`. Cells past the ends are 0 on a bounded row, and the row wraps
+// around on any other topology.
+fn next_row(row: &[u8], automaton: Automaton, topology: Topology) -> Vec<u8> {
+    let width = row.len();
+    let color = |x: isize| topology.wrap(x, 0, width, 1).map_or(0, |(x, _)| row[x]);
+    (0..width as isize)
+        .map(|x| automaton.next(color(x - 1), color(x), color(x + 1)))
+        .collect()
+}
+
+// Space-time diagram of `rows`, the first row on top, in the format of the path's
+// extension: a black and white PBM, or a grayscale PNG with colour 0 white and the
+// last colour black.
+fn save_diagram(path: &Path, rows: &[Vec<u8>], colors: u8) -> Result<(), String> {
+    let error = |e: &dyn fmt::Display| format!("{}: {e}", path.display());
+    let (width, height) = (rows.first().map_or(0, |row| row.len()), rows.len());
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let extension = extension.to_lowercase();
+    if extension != "pbm" && extension != "png" {
+        return Err(format!(
+            "{}: unknown extension, expected .pbm or .png",
+            path.display()
+        ));
+    }
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let mut out = BufWriter::new(file);
+
+    if extension == "pbm" {
+        // Plain PBM, 1 is black. Every cell not of colour 0 is black.
+        writeln!(out, "P1\n{width} {height}").map_err(|e| error(&e))?;
+        for line in rows.iter().flat_map(|row| row.chunks(PBM_CELLS_PER_LINE)) {
+            let line: Vec<&str> = line
+                .iter()
+                .map(|color| if *color == 0 { "0" } else { "1" })
+                .collect();
+            writeln!(out, "{}", line.join(" ")).map_err(|e| error(&e))?;
+        }
+        return out.flush().map_err(|e| error(&e));
+    }
+
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels: Vec<u8> = rows
+        .iter()
+        .flatten()
+        .map(|color| 255 - (*color as u32 * 255 / (colors as u32 - 1)) as u8)
+        .collect();
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+    writer.write_image_data(&pixels).map_err(|e| error(&e))
+}
+
+fn first_row_from_args(args: &Args, width: usize) -> Vec<u8> {
+    let seed = args.seed.unwrap_or_else(nannou::rand::random);
+    first_row(width, args.wolfram.colors(), args.start, args.density, seed)
+}
+
+// Cells per row, `--board-size` or as many as fit in the window.
+fn width_from_args(args: &Args) -> usize {
+    args.board_size
+        .map_or((WIDTH / BLOCK_SIZE) as usize, |size| size as usize)
+        .max(1)
+}
+
+// `--output`, or elementary.pbm since the Game of Life's .rle does not fit a diagram.
+fn output_from_args(args: &Args) -> PathBuf {
+    args.output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("elementary.pbm"))
+}
+
+// Writes the first row and `--generations` more to `--output`, without a window.
+fn run_headless(args: &Args) {
+    let mut rows = vec![first_row_from_args(args, width_from_args(args))];
+    for _ in 0..args.generations {
+        let next = next_row(rows.last().unwrap(), args.wolfram, args.topology);
+        rows.push(next);
+    }
+    let path = output_from_args(args);
+    match save_diagram(&path, &rows, args.wolfram.colors()) {
+        Ok(_) => eprintln!("Space-time diagram saved to {}", path.display()),
+        Err(e) => {
+            eprintln!("Cannot save space-time diagram {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+struct Model {
+    automaton: Automaton,
+    topology: Topology,
+    // The rows on screen, oldest first.
+    rows: VecDeque<Vec<u8>>,
+    // Rows computed after the first one.
+    generation: u64,
+    running: bool,
+    clock: Clock,
+}
+
+impl Model {
+    fn new(automaton: Automaton, topology: Topology, first: Vec<u8>) -> Self {
+        Model {
+            automaton,
+            topology,
+            rows: VecDeque::from([first]),
+            generation: 0,
+            running: false,
+            clock: Clock::new(DEFAULT_SPEED, MIN_SPEED, MAX_SPEED)
+                .with_max_steps_per_frame(MAX_STEPS_PER_FRAME),
+        }
+    }
+
+    // Adds the next row at the bottom, scrolling the oldest row out once the window is full.
+    fn step(&mut self) {
+        let next = next_row(self.rows.back().unwrap(), self.automaton, self.topology);
+        self.rows.push_back(next);
+        if self.rows.len() > (HEIGHT / BLOCK_SIZE) as usize {
+            self.rows.pop_front();
+        }
+        self.generation += 1;
+    }
+
+    // Runs as many steps as the clock asks for in `elapsed` time.
+    fn advance(&mut self, elapsed: Duration) {
+        for _ in 0..self.clock.steps(elapsed) {
+            self.step();
+        }
+    }
+}
+
+fn model(app: &App) -> Model {
+    app.new_window()
+        .view(view)
+        .size(WIDTH, HEIGHT)
+        .mouse_pressed(mouse_pressed)
+        .key_pressed(key_pressed)
+        .build()
+        .unwrap();
+
+    let args = ARGS.get().unwrap();
+    let first = first_row_from_args(args, width_from_args(args));
+    Model::new(args.wolfram, args.topology, first)
+}
+
+fn update(_app: &App, model: &mut Model, update: Update) {
+    if !model.running {
+        return;
+    }
+
+    model.advance(update.since_last);
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    frame.clear(BLACK);
+
+    let draw = app.draw();
+    let mut cell_stroke = BLACK;
+    if !model.running {
+        cell_stroke = PLUM;
+    }
+    draw.background().color(cell_stroke);
+
+    let block_size = BLOCK_SIZE as f32;
+    let colors = model.automaton.colors();
+    let left = -(WIDTH as f32) / 2.0 + block_size / 2.0;
+    let top = HEIGHT as f32 / 2.0 - block_size / 2.0;
+    for (row_i, row) in model.rows.iter().enumerate() {
+        for (x, color) in row.iter().enumerate().take((WIDTH / BLOCK_SIZE) as usize) {
+            let level = *color as f32 / (colors - 1) as f32;
+            draw.rect()
+                .color(rgb(level, level, level))
+                .stroke(cell_stroke)
+                .stroke_weight(1.0)
+                .x_y(
+                    left + x as f32 * block_size,
+                    top - row_i as f32 * block_size,
+                )
+                .w(block_size)
+                .h(block_size);
+        }
+    }
+
+    let hud = format!(
+        "rule {}\ngeneration {}\nspeed {} rows/s",
+        model.automaton, model.generation, model.clock.speed
+    );
+    draw.text(&hud)
+        .color(GOLD)
+        .font_size(14)
+        .left_justify()
+        .x_y(-(WIDTH as f32) / 2.0 + 100.0, HEIGHT as f32 / 2.0 - 30.0);
+
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn mouse_pressed(_app: &App, model: &mut Model, button: MouseButton) {
+    if MouseButton::Right == button {
+        model.running = !model.running;
+    }
+}
+
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    let args = ARGS.get().unwrap();
+    match key {
+        Key::Space => model.running = !model.running,
+        Key::Right if !model.running => model.step(),
+        Key::Up => model.clock.change_speed(true),
+        Key::Down => model.clock.change_speed(false),
+        // Start over, from a new random row with --start random.
+        Key::C => {
+            let first = first_row_from_args(args, model.rows[0].len());
+            *model = Model::new(model.automaton, model.topology, first);
+        }
+        // The rows on screen, as a space-time diagram.
+        Key::S => {
+            let rows: Vec<Vec<u8>> = model.rows.iter().cloned().collect();
+            let path = output_from_args(args);
+            match save_diagram(&path, &rows, model.automaton.colors()) {
+                Ok(_) => println!("Space-time diagram saved to {}", path.display()),
+                Err(e) => println!("Cannot save space-time diagram {e}"),
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test_elementary {
+    use super::*;
+
+    fn diagram(automaton: Automaton, width: usize, generations: usize) -> Vec<Vec<u8>> {
+        let mut rows = vec![first_row(width, automaton.colors(), Start::Single, 0.0, 0)];
+        for _ in 0..generations {
+            rows.push(next_row(rows.last().unwrap(), automaton, Topology::Bounded));
+        }
+        rows
+    }
+
+    #[test]
+    fn parse_automata() {
+        assert_eq!("30".parse(), Ok(Automaton::Elementary(30)));
+        assert_eq!(
+            "3:777".parse(),
+            Ok(Automaton::Totalistic {
+                colors: 3,
+                code: 777
+            })
+        );
+        assert_eq!("3:777".parse::<Automaton>().unwrap().to_string(), "3:777");
+        assert!("256".parse::<Automaton>().is_err());
+        assert!("8:1".parse::<Automaton>().is_err());
+        // Two colours have sums 0 to 3, so 16 codes.
+        assert!("2:16".parse::<Automaton>().is_err());
+    }
+
+    #[test]
+    fn rule_90_draws_sierpinski() {
+        let rows = diagram(Automaton::Elementary(90), 9, 3);
+        let text: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|c| if *c == 0 { '.' } else { '#' })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(text, ["....#....", "...#.#...", "..#...#..", ".#.#.#.#."]);
+
+        // Rule 30's centre column, a classic random number generator.
+        let rows = diagram(Automaton::Elementary(30), 41, 8);
+        let centre: Vec<u8> = rows.iter().map(|row| row[20]).collect();
+        assert_eq!(centre, [1, 1, 0, 1, 1, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn totalistic_digits() {
+        // 777 in base 3 is 1001210, the digits for sums 6 down to 0.
+        let automaton = Automaton::Totalistic {
+            colors: 3,
+            code: 777,
+        };
+        let next: Vec<u8> = [(0, 0, 0), (1, 0, 0), (1, 1, 0), (2, 1, 0), (2, 2, 2)]
+            .iter()
+            .map(|(l, c, r)| automaton.next(*l, *c, *r))
+            .collect();
+        assert_eq!(next, [0, 1, 2, 1, 1]);
+
+        // Two colours with digits 0110, elementary rule 126: alive unless all three cells agree.
+        let as_elementary = Automaton::Totalistic { colors: 2, code: 6 };
+        assert_eq!(
+            diagram(as_elementary, 21, 10),
+            diagram(Automaton::Elementary(126), 21, 10)
+        );
+    }
+
+    #[test]
+    fn wrapping_and_random_rows() {
+        let row = [1, 0, 0, 0];
+        assert_eq!(
+            next_row(&row, Automaton::Elementary(90), Topology::Torus),
+            [0, 1, 0, 1]
+        );
+        assert_eq!(
+            next_row(&row, Automaton::Elementary(90), Topology::Bounded),
+            [0, 1, 0, 0]
+        );
+
+        let random = first_row(100, 3, Start::Random, 0.5, 7);
+        assert_eq!(random, first_row(100, 3, Start::Random, 0.5, 7));
+        assert!(random.iter().all(|color| *color < 3));
+        assert!(random.contains(&2) && random.contains(&0));
+    }
+
+    #[test]
+    fn export_diagrams() {
+        let rows = diagram(Automaton::Elementary(90), 5, 1);
+        let dir = std::env::temp_dir();
+        let pbm = dir.join("elementary_test.pbm");
+        save_diagram(&pbm, &rows, 2).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&pbm).unwrap(),
+            "P1\n5 2\n0 0 1 0 0\n0 1 0 1 0\n"
+        );
+
+        // Wide rows are wrapped, every line within 70 characters.
+        save_diagram(&pbm, &diagram(Automaton::Elementary(90), 80, 3), 2).unwrap();
+        let text = std::fs::read_to_string(&pbm).unwrap();
+        assert!(text.lines().all(|line| line.len() <= 70));
+        assert_eq!(text.lines().count(), 2 + 4 * 3);
+
+        let png = dir.join("elementary_test.png");
+        save_diagram(&png, &rows, 2).unwrap();
+        let decoder = png::Decoder::new(File::open(&png).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(&pixels[..5], [255, 255, 0, 255, 255]);
+
+        assert!(save_diagram(&dir.join("elementary_test.rle"), &rows, 2).is_err());
+    }
+}
@@ -1,20 +1,32 @@
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
 
 use nannou::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cli_options::Args;
+use crate::snapshot;
 
 // Video
 // https://www.youtube.com/watch?v=L4u7Zy_b868
 //
+// Window size in pixels, unless --columns or --rows ask for another one.
 const WIDTH: u32 = 600;
 const HEIGHT: u32 = 600;
-// offset is half the window's with/height.
-const OFFSET: f32 = 300.;
-const SQUARE_WIDTH: u32 = 10; // In pixels
+
+// Columns a thread lets fall at once.
+const COLUMNS_PER_BAND: usize = 16;
 
 // Coloring
-static mut HUE: AtomicU32 = AtomicU32::new(200);
+static HUE: AtomicU32 = AtomicU32::new(200);
+
+// Command line options, read once the window model is built.
+static ARGS: OnceLock<Args> = OnceLock::new();
 
-pub fn run() {
+pub fn run(args: &Args) {
+    ARGS.set(args.clone()).unwrap();
     nannou::app(model).update(update).run();
 }
 
@@ -23,62 +35,232 @@ struct Model {
     grid: Grid,
     // pixel width
     w: usize,
+    // Frames the sand has fallen for.
+    generation: u64,
+    // Paused with Space.
+    running: bool,
+    // The grid follows the window size, unless --columns or --rows fixed it.
+    fit_window: bool,
+}
+
+// The sand, the hue the next grains get and where the fall is at, saved with `W` and
+// loaded with `--load`.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    grid: Grid,
+    hue: u32,
+    // Which way grains slide depends on the generation.
+    generation: u64,
+    running: bool,
 }
 
 fn model(app: &App) -> Model {
     // Setup
-    let width = WIDTH;
-    let height = HEIGHT;
-    let w = SQUARE_WIDTH.to_usize().unwrap();
+    let args = ARGS.get().unwrap();
+    let w = args.cell_size.to_usize().unwrap();
+    let cols = args.columns.unwrap_or(WIDTH / args.cell_size); // "cols = 60"
+    let rows = args.rows.unwrap_or(HEIGHT / args.cell_size); // "rows = 60"
 
-    let cols = width / SQUARE_WIDTH; // "cols = 60"
-    let rows = height / SQUARE_WIDTH; // "rows = 60"
+    let mut grid = make_2d_array(cols.try_into().unwrap(), rows.try_into().unwrap());
+    let (mut generation, mut running) = (0, true);
+    if let Some(path) = &args.load {
+        let session: Session =
+            snapshot::load(path).unwrap_or_else(|e| panic!("Cannot load session {e}"));
+        // The window takes the size of the saved sand.
+        grid = session.grid;
+        HUE.store(session.hue, Ordering::Relaxed);
+        (generation, running) = (session.generation, session.running);
+    }
+    let width = (grid.len() * w) as u32;
+    let height = (grid[0].len() * w) as u32;
 
     app.new_window()
         .size(width, height)
         .view(view)
+        .resized(resized)
         .mouse_pressed(mouse_pressed)
         .mouse_moved(mouse_moved)
+        .key_pressed(key_pressed)
         .build()
         .unwrap();
 
-    let grid = make_2d_array(cols.try_into().unwrap(), rows.try_into().unwrap());
-    Model { grid, w }
+    Model {
+        grid,
+        w,
+        generation,
+        running,
+        fit_window: args.columns.is_none() && args.rows.is_none(),
+    }
+}
+
+// A grid that fills the window gains or loses columns and rows evenly on both sides,
+// so the sand stays where it is on screen, and falls to the new floor.
+fn resized(_app: &App, model: &mut Model, size: Vec2) {
+    if !model.fit_window {
+        return;
+    }
+    let cols = ((size.x / model.w as f32) as usize).max(1);
+    let rows = ((size.y / model.w as f32) as usize).max(1);
+    model.grid = resize_grid(&model.grid, cols, rows);
+}
+
+// The grid with `cols` x `rows` cells around the same middle. Grains past the new edges
+// are dropped.
+fn resize_grid(grid: &Grid, cols: usize, rows: usize) -> Grid {
+    let shift = |old: usize, new: usize| (new as isize - old as isize) / 2;
+    let (dx, dy) = (shift(grid.len(), cols), shift(grid[0].len(), rows));
+    let mut resized = make_2d_array(cols, rows);
+    for (col_i, col) in grid.iter().enumerate() {
+        for (row_i, &row) in col.iter().enumerate() {
+            let (Ok(x), Ok(y)) = (
+                usize::try_from(col_i as isize + dx),
+                usize::try_from(row_i as isize + dy),
+            ) else {
+                continue;
+            };
+            if x < cols && y < rows {
+                resized[x][y] = row;
+            }
+        }
+    }
+    resized
 }
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    let mut next_generation_grid = make_2d_array(model.grid.len(), model.grid[0].len());
-    let grid_limit = model.grid.len();
-    for col_i in 0..model.grid.len() {
-        let col = model.grid[col_i].clone();
-        for row_i in 0..col.len() {
-            let row = col[row_i];
-            if row >= 1 && row_i > 0 {
-                // Sand should look like going down smooth.
-                let mut direction: isize = -1;
-                if random_range(0., 1.) >= 0.5 {
-                    direction = 1;
-                }
-                let index_a = match_boundaries(col_i.to_isize().unwrap() + direction, grid_limit);
-                let lateral_a = model.grid[index_a][row_i - 1];
-                let index_b = match_boundaries(col_i.to_isize().unwrap() - direction, grid_limit);
-                let lateral_b = model.grid[index_b][row_i - 1];
-
-                if next_generation_grid[col_i][row_i - 1] == 0 {
-                    next_generation_grid[col_i][row_i - 1] = row;
-                } else if lateral_a == 0 {
-                    next_generation_grid[index_a][row_i - 1] = row;
-                } else if lateral_b == 0 {
-                    next_generation_grid[index_b][row_i - 1] = row;
-                } else {
-                    next_generation_grid[col_i][row_i] = row;
+    if !model.running {
+        return;
+    }
+    model.grid = next_generation(&model.grid, model.generation);
+    model.generation += 1;
+}
+
+// Every grain falls one cell, straight down or else to one side, column after column
+// from the left: a grain falls straight when no grain landed there before it, and a grain
+// landing on the side takes the place of one that was there.
+//
+// A column only sees the grains the column on its left dropped into it, so bands of
+// columns fall in parallel, each guessing that nothing came in from its left. The guesses
+// are then checked from the left, and a column guessed wrong falls again. Which side a
+// grain tries first comes from the generation and its cell, so the sand falls the same way
+// with any number of threads.
+fn next_generation(grid: &Grid, generation: u64) -> Grid {
+    let rows = grid[0].len();
+    let mut falls: Vec<ColumnFall> = (0..grid.len().div_ceil(COLUMNS_PER_BAND))
+        .into_par_iter()
+        .flat_map_iter(|band| {
+            let start = band * COLUMNS_PER_BAND;
+            let end = (start + COLUMNS_PER_BAND).min(grid.len());
+            let mut from_left = vec![0; rows];
+            (start..end).map(move |col_i| {
+                let fall = ColumnFall::new(grid, generation, col_i, from_left.clone());
+                from_left.clone_from(&fall.to_right);
+                fall
+            })
+        })
+        .collect();
+    for col_i in 1..falls.len() {
+        if falls[col_i].from_left != falls[col_i - 1].to_right {
+            let from_left = falls[col_i - 1].to_right.clone();
+            falls[col_i] = ColumnFall::new(grid, generation, col_i, from_left);
+        }
+    }
+
+    (0..falls.len())
+        .into_par_iter()
+        .map(|col_i| {
+            let mut col = falls[col_i].own.clone();
+            // The column on the right fell after this one.
+            if let Some(right) = falls.get(col_i + 1) {
+                for (cell, &grain) in col.iter_mut().zip(&right.to_left) {
+                    if grain != 0 {
+                        *cell = grain;
+                    }
                 }
-            } else if row >= 1 {
-                next_generation_grid[col_i][row_i] = row;
             }
+            col
+        })
+        .collect()
+}
+
+// Where the grains of one column went, 0 for the cells no grain landed in.
+struct ColumnFall {
+    // Grains the column on the left dropped into this one.
+    from_left: Vec<u32>,
+    // This column once its own grains fell too.
+    own: Vec<u32>,
+    to_left: Vec<u32>,
+    to_right: Vec<u32>,
+}
+
+impl ColumnFall {
+    fn new(grid: &Grid, generation: u64, col_i: usize, from_left: Vec<u32>) -> Self {
+        let rows = grid[0].len();
+        let band_start = col_i.saturating_sub(1);
+        let band_end = (col_i + 1).min(grid.len() - 1);
+        let mut band = make_2d_array(band_end + 1 - band_start, rows);
+        band[col_i - band_start].clone_from(&from_left);
+        fall_column(grid, generation, col_i, &mut band, band_start);
+
+        // The edge columns have no column past them.
+        let to_right = match band_end > col_i {
+            true => band.pop().unwrap(),
+            false => vec![0; rows],
+        };
+        let own = band.pop().unwrap();
+        let to_left = band.pop().unwrap_or_else(|| vec![0; rows]);
+        ColumnFall {
+            from_left,
+            own,
+            to_left,
+            to_right,
+        }
+    }
+}
+
+// Moves the grains of column `col_i` into `band`, the columns of the next generation
+// from `band_start` on.
+fn fall_column(
+    grid: &Grid,
+    generation: u64,
+    col_i: usize,
+    band: &mut [Vec<u32>],
+    band_start: usize,
+) {
+    let grid_limit = grid.len();
+    for (row_i, &row) in grid[col_i].iter().enumerate() {
+        if row >= 1 && row_i > 0 {
+            // Sand should look like going down smooth.
+            let mut direction: isize = -1;
+            if falls_right_first(generation, col_i, row_i) {
+                direction = 1;
+            }
+            let index_a = match_boundaries(col_i.to_isize().unwrap() + direction, grid_limit);
+            let lateral_a = grid[index_a][row_i - 1];
+            let index_b = match_boundaries(col_i.to_isize().unwrap() - direction, grid_limit);
+            let lateral_b = grid[index_b][row_i - 1];
+
+            if band[col_i - band_start][row_i - 1] == 0 {
+                band[col_i - band_start][row_i - 1] = row;
+            } else if lateral_a == 0 {
+                band[index_a - band_start][row_i - 1] = row;
+            } else if lateral_b == 0 {
+                band[index_b - band_start][row_i - 1] = row;
+            } else {
+                band[col_i - band_start][row_i] = row;
+            }
+        } else if row >= 1 {
+            band[col_i - band_start][row_i] = row;
         }
     }
-    model.grid = next_generation_grid;
+}
+
+// A coin flip per grain and generation, from a SplitMix64 hash of both.
+fn falls_right_first(generation: u64, col_i: usize, row_i: usize) -> bool {
+    let mut z =
+        generation.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ ((col_i as u64) << 32 | row_i as u64);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)) & 1 == 1
 }
 
 fn match_boundaries(col: isize, limit: usize) -> usize {
@@ -93,6 +275,9 @@ fn view(app: &App, model: &Model, frame: Frame) {
 
     let draw = app.draw();
     draw.background().color(PLUM);
+    // The grid is centred in the window.
+    let offset_x = (model.grid.len() * model.w) as f32 / 2.0 - (model.w as f32 / 2.0);
+    let offset_y = (model.grid[0].len() * model.w) as f32 / 2.0 - (model.w as f32 / 2.0);
     // Draw the squares
     for col_i in 0..model.grid.len() {
         let col = model.grid[col_i].clone();
@@ -107,16 +292,14 @@ fn view(app: &App, model: &Model, frame: Frame) {
 
             let fill_color = hsla(f32::from_u32(row).unwrap() / 3600., 1.0, light, 1.);
 
-            // OFFSET ("300") is half the window's with/height.
-            let offset = OFFSET - (model.w as f32 / 2.0);
             draw.rect()
                 // .color(rgb_u32(fill_color))
                 .color(fill_color)
                 .stroke(stroke)
                 .stroke_weight(1.0)
                 .x_y(
-                    (col_i * model.w) as f32 - offset,
-                    (row_i * model.w) as f32 - offset,
+                    (col_i * model.w) as f32 - offset_x,
+                    (row_i * model.w) as f32 - offset_y,
                 )
                 .w(model.w as f32)
                 .h(model.w as f32);
@@ -156,36 +339,125 @@ fn mouse_moved(app: &App, model: &mut Model, point: Point2) {
     increase_hue();
 }
 
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    match key {
+        Key::Space => model.running = !model.running,
+        Key::W => save_session(model),
+        _ => {}
+    }
+}
+
+fn save_session(model: &Model) {
+    let path = ARGS
+        .get()
+        .unwrap()
+        .session
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("falling_sand_session.json"));
+    let session = Session {
+        grid: model.grid.clone(),
+        hue: HUE.load(Ordering::Relaxed),
+        generation: model.generation,
+        running: model.running,
+    };
+    match snapshot::save(&path, &session) {
+        Ok(_) => println!("Session saved to {}", path.display()),
+        Err(e) => println!("Cannot save session {e}"),
+    }
+}
+
 fn update_mouse_grid(model: &mut Model, point: Point2) {
-    let offset = OFFSET;
-    let x = constraint_to_grid(point.x, offset);
-    let y = constraint_to_grid(point.y, offset);
+    let x = constraint_to_grid(point.x, model.grid.len(), model.w);
+    let y = constraint_to_grid(point.y, model.grid[0].len(), model.w);
 
-    let hue: u32 = unsafe { HUE.load(Ordering::Relaxed) };
+    let hue: u32 = HUE.load(Ordering::Relaxed);
     model.grid[x][y] = hue.clone();
 
-    unsafe { HUE.store(hue, Ordering::Relaxed) };
+    HUE.store(hue, Ordering::Relaxed);
 }
 
-fn constraint_to_grid(p: f32, offset: f32) -> usize {
+// Cell along one axis of `cells` cells of `w` pixels, centred in the window,
+// under the window coordinate `p`. Points past the grid go to its edge cells.
+fn constraint_to_grid(p: f32, cells: usize, w: usize) -> usize {
+    // Half the grid's width or height in pixels.
+    let offset = (cells * w) as f32 / 2.;
     let point = match p {
         p if p > offset => offset - 1.,
         p if p < -offset => -offset + 1.,
         p => p,
     };
-    ((point + offset) / (SQUARE_WIDTH).to_f32().unwrap())
+    ((point + offset) / w.to_f32().unwrap())
         .floor()
         .abs()
         .to_usize()
         .unwrap()
+        .min(cells - 1)
 }
 
 fn increase_hue() {
-    let hue: u32 = unsafe { HUE.load(Ordering::Relaxed) };
+    let hue: u32 = HUE.load(Ordering::Relaxed);
 
     let mut new_hue = hue + 2;
     if new_hue > 3580 {
         new_hue = 1;
     }
-    unsafe { HUE.store(new_hue, Ordering::Relaxed) };
+    HUE.store(new_hue, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod test_falling_sand {
+    use super::*;
+
+    // The loop the sand fell with before it ran in parallel, one column after the other.
+    fn sequential_generation(grid: &Grid, generation: u64) -> Grid {
+        let mut next_generation_grid = make_2d_array(grid.len(), grid[0].len());
+        for col_i in 0..grid.len() {
+            fall_column(grid, generation, col_i, &mut next_generation_grid, 0);
+        }
+        next_generation_grid
+    }
+
+    #[test]
+    fn rectangular_grids() {
+        // 80 columns by 30 rows of 10 pixels, an 800 x 300 window.
+        assert_eq!(constraint_to_grid(-400.0, 80, 10), 0);
+        assert_eq!(constraint_to_grid(-0.5, 80, 10), 39);
+        assert_eq!(constraint_to_grid(399.0, 80, 10), 79);
+        assert_eq!(constraint_to_grid(1000.0, 80, 10), 79);
+        assert_eq!(constraint_to_grid(-149.0, 30, 10), 0);
+        assert_eq!(constraint_to_grid(0.0, 30, 10), 15);
+        assert_eq!(constraint_to_grid(149.5, 30, 10), 29);
+
+        // Growing and shrinking around the middle.
+        let mut grid = make_2d_array(4, 2);
+        grid[1][0] = 7;
+        let wider = resize_grid(&grid, 8, 4);
+        assert_eq!(wider[3][1], 7);
+        assert_eq!(resize_grid(&wider, 4, 2), grid);
+        assert_eq!(resize_grid(&grid, 1, 1), vec![vec![7]]);
+    }
+
+    #[test]
+    fn parallel_fall_matches_sequential() {
+        // Ten times the window, widths that leave short bands, and the edge columns alone.
+        for (cols, rows) in [(600, 600), (33, 40), (47, 40), (1, 5), (2, 5)] {
+            let mut grid = make_2d_array(cols, rows);
+            for (col_i, col) in grid.iter_mut().enumerate() {
+                for (row_i, cell) in col.iter_mut().enumerate() {
+                    if falls_right_first(u64::MAX, col_i, row_i) {
+                        *cell = (col_i * rows + row_i) as u32 % 3600 + 1;
+                    }
+                }
+            }
+            for generation in 0..10 {
+                let next = next_generation(&grid, generation);
+                assert_eq!(
+                    next,
+                    sequential_generation(&grid, generation),
+                    "{cols}x{rows}"
+                );
+                grid = next;
+            }
+        }
+    }
 }
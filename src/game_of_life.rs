@@ -1,42 +1,164 @@
 use std::isize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
 
+use clap::ValueEnum;
 use nannou::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::cli_options::Args;
+use crate::snapshot;
+use age::Ages;
+pub use age::Gradient;
+use bitgrid::BitGrid;
+pub use camera::Camera;
+pub use controls::{zoom_factor, Clock, Navigation};
+use detector::{Classification, Detector};
+use drawing::Shape;
+pub use hashlife::MAX_STEP_EXPONENT;
+use hashlife::{Root, Universe};
+use history::History;
+pub use pattern::{place_cells_centered, read_rle_cells};
+pub use rule::Rule;
+use session::Session;
+pub use soup::Symmetry;
+use sparse::Sparse;
+use stamps::Stamp;
+use stats::Stats;
+use tiling::Tiling;
+pub use topology::Topology;
+
+mod age;
+mod bitgrid;
+mod camera;
+mod controls;
+mod detector;
+mod drawing;
+mod hashlife;
+mod headless;
+mod history;
+mod larger;
+mod pattern;
+mod rule;
+mod session;
+mod soup;
+mod sparse;
+mod stamps;
+mod stats;
+mod tiling;
+mod topology;
+
+// Window size in pixels, unless --columns or --rows ask for another one.
 const WIDTH: u32 = 600;
 const HEIGHT: u32 = WIDTH;
-const MIDDLE: u32 = WIDTH / 2;
 const BLOCK_SIZE: u32 = 10; // width and height in pixels
 
-pub fn run() {
+// Edits kept for undo, and generations kept for stepping back.
+const HISTORY_LEN: usize = 100;
+// Large bit grid boards keep fewer past states, to stay around this many bytes in each history.
+const HISTORY_BYTES: usize = 64 << 20;
+// Steps per second while running, about one per frame to start with.
+const DEFAULT_SPEED: f64 = 60.0;
+const MIN_SPEED: f64 = 0.5;
+const MAX_SPEED: f64 = 3840.0;
+// A frame never runs more steps than this, a slow engine drops the rest.
+const MAX_STEPS_PER_FRAME: f64 = 128.0;
+// Smallest band of grid columns a thread steps at once.
+const COLUMNS_PER_TASK: usize = 8;
+
+// Shown by the help overlay.
+const KEY_BINDINGS: [(&str, &str); 25] = [
+    ("Space", "run or pause"),
+    ("Right", "one step, while paused"),
+    ("Left", "one step back, while paused"),
+    ("Up / Down", "faster / slower"),
+    ("C", "clear"),
+    ("N", "new random soup"),
+    ("I", "invert the window"),
+    ("Z / Y", "undo / redo"),
+    ("S", "save the pattern"),
+    ("E", "save the statistics"),
+    ("W", "save the session"),
+    ("D", "classify the pattern, or stop"),
+    ("1 to 6", "pick a stamp"),
+    ("R / F", "rotate / flip the stamp"),
+    ("Escape", "drop the stamp"),
+    ("Left drag", "paint cells, or place the stamp"),
+    ("Right / Ctrl drag", "erase cells"),
+    ("Shift drag", "draw a line or rectangle"),
+    ("T", "line / rectangle / filled"),
+    ("Middle drag", "pan"),
+    ("Wheel", "zoom"),
+    ("F11", "fullscreen"),
+    ("H", "show or hide this help"),
+    ("", ""),
+    ("", "PLUM background: paused"),
+];
+
+// Command line options, read once the window model is built.
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+pub fn run(args: &Args) {
+    if args.headless {
+        return headless::run(args);
+    }
+    ARGS.set(args.clone()).unwrap();
     nannou::app(model).update(update).run();
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum GridStatus {
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GridStatus {
     LIFE,
     NOT,
+    // Generations rules: generations since the cell stopped being alive.
+    // Dying cells are not live neighbours and cannot be born again until dead.
+    DYING(u8),
 }
 
+// Colours for the dying states, from just dead onwards.
+const DYING_COLORS: [nannou::color::Rgb8; 6] =
+    [STEELBLUE, MEDIUMPURPLE, CRIMSON, DARKORANGE, GOLD, DIMGRAY];
+
 impl GridStatus {
     fn color(&self) -> nannou::color::Rgb8 {
         match self {
             GridStatus::LIFE => WHITE,
             GridStatus::NOT => BLACK,
+            GridStatus::DYING(age) => DYING_COLORS[(*age as usize - 1) % DYING_COLORS.len()],
+        }
+    }
+    // Number of the state in multi-state RLE patterns: 0 dead, 1 alive, then the dying ones.
+    pub fn state(&self) -> u8 {
+        match self {
+            GridStatus::NOT => 0,
+            GridStatus::LIFE => 1,
+            GridStatus::DYING(age) => age + 1,
+        }
+    }
+
+    pub fn from_state(state: u8) -> GridStatus {
+        match state {
+            0 => GridStatus::NOT,
+            1 => GridStatus::LIFE,
+            state => GridStatus::DYING(state - 1),
         }
     }
+
     fn toggle(&self) -> GridStatus {
         match self {
             GridStatus::LIFE => GridStatus::NOT,
-            GridStatus::NOT => GridStatus::LIFE,
+            GridStatus::NOT | GridStatus::DYING(_) => GridStatus::LIFE,
         }
     }
     fn grid_value(&self) -> u8 {
         match self {
             GridStatus::LIFE => 1,
-            GridStatus::NOT => 0,
+            GridStatus::NOT | GridStatus::DYING(_) => 0,
         }
     }
-    fn game_rules(&self, neighbors: &mut [u8; 9]) -> GridStatus {
+    fn game_rules(&self, neighbors: &mut [u8; 9], rule: &Rule) -> GridStatus {
         let x_y = neighbors[4];
         neighbors[4] = 0;
         let mut total_neighbors = 0;
@@ -45,21 +167,36 @@ impl GridStatus {
                 total_neighbors += 1;
             }
         }
+        let cell = match self {
+            GridStatus::DYING(_) => *self,
+            _ if x_y == 1 => GridStatus::LIFE,
+            _ => GridStatus::NOT,
+        };
+        cell.next(total_neighbors, rule)
+    }
+    // The cell's next state, given how many of its neighbours are alive.
+    fn next(&self, total_neighbors: usize, rule: &Rule) -> GridStatus {
+        // Dying cells only count down, whatever their neighbours.
+        if let GridStatus::DYING(age) = self {
+            return match age + 2 < rule.states() {
+                true => GridStatus::DYING(age + 1),
+                false => GridStatus::NOT,
+            };
+        }
 
-        if x_y == 1 && total_neighbors < 2 {
-            // 1. Any live cell with fewer than two live neighbours dies, as if by underpopulation.
-            return GridStatus::NOT;
-        } else if x_y == 1 && (total_neighbors == 2 || total_neighbors == 3) {
-            // 2. Any live cell with two or three live neighbours lives on to the next generation.
-            return GridStatus::LIFE;
-        } else if x_y == 1 && total_neighbors > 3 {
-            // 3. Any live cell with more than three live neighbours dies, as if by overpopulation.
-            return GridStatus::NOT;
-        } else if x_y == 0 && total_neighbors == 3 {
-            // 4. Any dead cell with exactly three live neighbours becomes a live cell, as if by reproduction.
+        // Conway's B3/S23 reads as:
+        // 1. Any live cell with fewer than two live neighbours dies, as if by underpopulation.
+        // 2. Any live cell with two or three live neighbours lives on to the next generation.
+        // 3. Any live cell with more than three live neighbours dies, as if by overpopulation.
+        // 4. Any dead cell with exactly three live neighbours becomes a live cell, as if by reproduction.
+        let alive = *self == GridStatus::LIFE;
+        if rule.next(alive, total_neighbors) {
             return GridStatus::LIFE;
         }
-        return self.clone();
+        if alive && rule.states() > 2 {
+            return GridStatus::DYING(1);
+        }
+        GridStatus::NOT
     }
 }
 
@@ -71,6 +208,7 @@ mod test_grid_status {
     struct NeighborsTest {
         neighbors: [u8; 9],
         name: String,
+        rule: &'static str,
         expected: GridStatus,
     }
     #[test]
@@ -79,78 +217,1105 @@ mod test_grid_status {
             NeighborsTest {
                 neighbors: [0, 0, 0, 0, 0, 0, 0, 0, 0],
                 name: "all_dead".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::NOT,
             },
             NeighborsTest {
                 neighbors: [0, 0, 0, 0, 1, 0, 0, 0, 0],
                 name: "all_dead_core_alive".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::NOT,
             },
             NeighborsTest {
                 neighbors: [1, 0, 0, 0, 1, 0, 0, 0, 0],
                 name: "core_alive_rule_1".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::NOT,
             },
             NeighborsTest {
                 neighbors: [1, 1, 0, 0, 1, 0, 0, 0, 0],
                 name: "core_alive_rule_2_with_two".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::LIFE,
             },
             NeighborsTest {
                 neighbors: [1, 1, 0, 0, 1, 0, 0, 0, 0],
                 name: "core_alive_rule_2_with_three".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::LIFE,
             },
             NeighborsTest {
                 neighbors: [1, 1, 1, 0, 1, 0, 1, 0, 0],
                 name: "core_alive_rule_3".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::NOT,
             },
             NeighborsTest {
                 neighbors: [1, 1, 1, 1, 1, 1, 1, 1, 1],
                 name: "core_alive_all_alive".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::NOT,
             },
             NeighborsTest {
                 neighbors: [1, 1, 0, 0, 0, 0, 1, 0, 0],
                 name: "core_alive_rule_4".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::LIFE,
             },
             NeighborsTest {
                 neighbors: [1, 1, 1, 0, 0, 0, 1, 0, 0],
                 name: "core_alive_rule_4_fail".to_string(),
+                rule: Rule::CONWAY,
                 expected: GridStatus::NOT,
             },
+            NeighborsTest {
+                neighbors: [1, 1, 1, 0, 0, 1, 1, 1, 0],
+                name: "high_life_birth_with_six".to_string(),
+                rule: Rule::HIGH_LIFE,
+                expected: GridStatus::LIFE,
+            },
+            NeighborsTest {
+                neighbors: [1, 1, 0, 0, 1, 0, 0, 0, 0],
+                name: "high_life_survive_with_two".to_string(),
+                rule: Rule::HIGH_LIFE,
+                expected: GridStatus::LIFE,
+            },
+            NeighborsTest {
+                neighbors: [1, 0, 0, 0, 0, 0, 0, 0, 1],
+                name: "seeds_birth_with_two".to_string(),
+                rule: Rule::SEEDS,
+                expected: GridStatus::LIFE,
+            },
+            NeighborsTest {
+                neighbors: [1, 0, 0, 0, 1, 0, 0, 0, 1],
+                name: "seeds_never_survive".to_string(),
+                rule: Rule::SEEDS,
+                expected: GridStatus::NOT,
+            },
+            NeighborsTest {
+                neighbors: [1, 1, 1, 1, 0, 1, 1, 1, 1],
+                name: "day_and_night_birth_with_eight".to_string(),
+                rule: Rule::DAY_AND_NIGHT,
+                expected: GridStatus::LIFE,
+            },
+            NeighborsTest {
+                neighbors: [1, 1, 0, 0, 1, 0, 0, 0, 0],
+                name: "day_and_night_dies_with_two".to_string(),
+                rule: Rule::DAY_AND_NIGHT,
+                expected: GridStatus::NOT,
+            },
+            NeighborsTest {
+                neighbors: [1, 1, 1, 1, 1, 0, 0, 0, 0],
+                name: "day_and_night_survive_with_four".to_string(),
+                rule: Rule::DAY_AND_NIGHT,
+                expected: GridStatus::LIFE,
+            },
         ];
 
         for n in neighbohr_blocks.iter_mut() {
-            let calculation = GridStatus::NOT.game_rules(&mut n.neighbors);
+            let rule = n.rule.parse::<Rule>().unwrap();
+            let calculation = GridStatus::NOT.game_rules(&mut n.neighbors, &rule);
             assert_eq!(calculation, n.expected, "`{0}` `{calculation:?}`", n.name);
         }
     }
+
+    #[test]
+    fn grid_generations_rules() {
+        let brians_brain = Rule::BRIANS_BRAIN.parse::<Rule>().unwrap();
+        let star_wars = Rule::STAR_WARS.parse::<Rule>().unwrap();
+        let two_alive = [1, 0, 0, 0, 0, 0, 0, 0, 1];
+
+        // Brian's Brain: born with 2, alive for one generation, dying for one.
+        let born = GridStatus::NOT.game_rules(&mut two_alive.clone(), &brians_brain);
+        assert_eq!(born, GridStatus::LIFE);
+        let mut alive = [1, 0, 0, 0, 1, 0, 0, 0, 1];
+        let dying = GridStatus::LIFE.game_rules(&mut alive, &brians_brain);
+        assert_eq!(dying, GridStatus::DYING(1));
+        let dead = dying.game_rules(&mut two_alive.clone(), &brians_brain);
+        assert_eq!(dead, GridStatus::NOT);
+
+        // Star Wars: two dying states, and dying cells are not live neighbours.
+        let dying = GridStatus::DYING(1).game_rules(&mut two_alive.clone(), &star_wars);
+        assert_eq!(dying, GridStatus::DYING(2));
+        let dead = dying.game_rules(&mut two_alive.clone(), &star_wars);
+        assert_eq!(dead, GridStatus::NOT);
+        assert_eq!(GridStatus::DYING(2).grid_value(), 0);
+        let mut survive = [1, 1, 1, 0, 1, 1, 0, 0, 0];
+        let alive = GridStatus::LIFE.game_rules(&mut survive, &star_wars);
+        assert_eq!(alive, GridStatus::LIFE);
+    }
 }
 
-type Grid = Vec<Vec<GridStatus>>;
+pub type Grid = Vec<Vec<GridStatus>>;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum Engine {
+    // Every cell of the window grid, every frame.
+    #[default]
+    Grid,
+    // Unbounded quadtree, the window grid is a viewport on it.
+    Hashlife,
+    // 64 cells per word on a board that can be larger than the window,
+    // the window grid is a viewport on it.
+    BitGrid,
+    // Unbounded hash map of the live cells, the window grid is a viewport on it.
+    Sparse,
+}
+
+#[derive(Debug, Clone)]
+enum Backend {
+    Grid,
+    Hashlife {
+        universe: Box<Universe>,
+        // Each update advances 2^step_exponent generations.
+        step_exponent: u8,
+    },
+    BitGrid(Box<BitGrid>),
+    Sparse(Sparse),
+}
+
+// A past state of the simulation, for undo and stepping back.
+// Hashlife keeps its root node, with the rest of the quadtree and the memo shared.
+#[derive(Debug, Clone)]
+enum Board {
+    Grid,
+    Hashlife(Root),
+    BitGrid(Box<BitGrid>),
+    Sparse(Sparse),
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    grid: Grid,
+    board: Board,
+    generation: u64,
+}
 
 #[derive(Debug, Clone)]
 struct Model {
     grid: Grid,
     running: bool,
+    rule: Rule,
+    topology: Topology,
+    backend: Backend,
+    generation: u64,
+    // Looks for still lifes, oscillators and spaceships while on, at the cost of going
+    // through every live cell each step.
+    detect: bool,
+    detector: Detector,
+    stats: Stats,
+    // States from before each edit, and from before each generation.
+    edits: History<Snapshot>,
+    generations: History<Snapshot>,
+    // Pattern placed by a left click instead of painting cells.
+    stamp: Option<Stamp>,
+    // Cells being painted or erased by a mouse drag.
+    stroke: Option<Stroke>,
+    // Drawn by dragging with Shift held.
+    shape: Shape,
+    camera: Camera,
+    // World coordinates of `grid[0][0]`. The grid engine's world is the grid itself,
+    // the others show the cells the camera sees in the grid.
+    origin: (i64, i64),
+    navigation: Navigation,
+    clock: Clock,
+    // Seed of the last random soup, to regenerate it.
+    seed: Option<u64>,
+    show_help: bool,
+    // Colours of live cells by age, and generations dead cells stay as ghosts.
+    // Started on the first step that needs them.
+    ages: Option<Ages>,
+    gradient: Gradient,
+    trail: u32,
+    // Window size in pixels.
+    window: (f32, f32),
+    // Cell size the grid engine's grid fills the window with as it is resized,
+    // `None` for a grid of fixed --columns and --rows.
+    fit_cell_size: Option<f32>,
+}
+
+// A mouse drag editing the cells, from the press to the release, in world cells.
+#[derive(Debug, Clone)]
+struct Stroke {
+    // LIFE paints, NOT erases.
+    status: GridStatus,
+    // Drawn from `start` to `end` on release, or `None` to paint along the way.
+    shape: Option<Shape>,
+    start: (i64, i64),
+    end: (i64, i64),
 }
 
 impl Model {
-    fn new(grid_width: Option<u32>) -> Self {
-        let grid_size = grid_width.unwrap_or_else(|| WIDTH) / BLOCK_SIZE;
-        let grid = new_square_grid(GridStatus::NOT, grid_size);
+    // An empty grid of `grid_size` columns and rows, by default the cells of BLOCK_SIZE
+    // pixels filling the window.
+    fn new(grid_size: Option<(usize, usize)>) -> Self {
+        let (columns, rows) = grid_size.unwrap_or((
+            (WIDTH / BLOCK_SIZE) as usize,
+            (HEIGHT / BLOCK_SIZE) as usize,
+        ));
+        let grid = new_grid(GridStatus::NOT, columns, rows);
         Self {
             grid,
             running: false,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            backend: Backend::Grid,
+            generation: 0,
+            detect: false,
+            detector: Detector::default(),
+            stats: Stats::default(),
+            edits: History::new(HISTORY_LEN),
+            generations: History::new(HISTORY_LEN),
+            stamp: None,
+            stroke: None,
+            shape: Shape::default(),
+            camera: Camera::new((columns as f32 / 2.0, rows as f32 / 2.0), BLOCK_SIZE as f32),
+            origin: (0, 0),
+            navigation: Navigation::default(),
+            clock: Clock::new(DEFAULT_SPEED, MIN_SPEED, MAX_SPEED)
+                .with_max_steps_per_frame(MAX_STEPS_PER_FRAME),
+            seed: None,
+            show_help: false,
+            ages: None,
+            gradient: Gradient::default(),
+            trail: 0,
+            window: (WIDTH as f32, HEIGHT as f32),
+            fit_cell_size: Some(BLOCK_SIZE as f32),
+        }
+    }
+
+    // Builds the sketch from the command line: pattern or soup, rule, topology and engine.
+    fn from_args(args: &Args) -> Self {
+        if let Some(path) = &args.load {
+            return snapshot::load(path)
+                .and_then(Model::from_session)
+                .unwrap_or_else(|e| panic!("Cannot load session {e}"));
+        }
+        let cell_size = args.cell_size as f32;
+        let window = window_size(args);
+        let columns = (window.0 / cell_size) as usize;
+        let rows = (window.1 / cell_size) as usize;
+        let fixed = args.columns.is_some() || args.rows.is_some();
+        let mut model = Model {
+            topology: args.topology,
+            detect: args.detect,
+            gradient: args.gradient.clone(),
+            trail: args.trail,
+            window,
+            fit_cell_size: (!fixed).then_some(cell_size),
+            ..Model::new(Some((columns, rows)))
+        };
+        model.camera.cell_size = cell_size;
+        let pattern = match args.soup {
+            true => {
+                let seed = args.seed.unwrap_or_else(nannou::rand::random);
+                model.seed = Some(seed);
+                Some(soup_from_args(args, seed))
+            }
+            false => args.pattern.as_ref().map(|path| {
+                pattern::load(path).unwrap_or_else(|e| panic!("Cannot load pattern {e}"))
+            }),
+        };
+        if let Some(pattern) = &pattern {
+            pattern::place_centered(&mut model.grid, pattern);
+            model.rule = pattern.rule.unwrap_or_default();
+        }
+        // An explicit `--rule` wins over the one in the pattern file.
+        if let Some(rule) = args.rule {
+            model.rule = rule;
+        }
+        match args.engine {
+            Engine::Grid => {}
+            Engine::Hashlife | Engine::BitGrid if model.rule.states() > 2 => {
+                panic!("Generations rule {} needs the grid engine", model.rule);
+            }
+            _ if model.rule.tiling() != Tiling::Square => {
+                panic!("{:?} cells need the grid engine", model.rule.tiling());
+            }
+            _ if model.rule.larger().is_some() => {
+                panic!("Larger than Life rule {} needs the grid engine", model.rule);
+            }
+            Engine::Hashlife => {
+                // The universe gets the whole pattern, not only what fits in the window.
+                let cells = pattern.map_or_else(|| model.grid.clone(), |pattern| pattern.cells);
+                model
+                    .use_hashlife(&cells, args.step_exponent)
+                    .unwrap_or_else(|e| panic!("Cannot start hashlife {e}"));
+            }
+            Engine::Sparse => {
+                let cells = pattern.map_or_else(|| model.grid.clone(), |pattern| pattern.cells);
+                model
+                    .use_sparse(&cells)
+                    .unwrap_or_else(|e| panic!("Cannot start the sparse universe {e}"));
+            }
+            Engine::BitGrid => {
+                let window_size = model.grid.len().max(model.grid[0].len());
+                let board_size = (args.board_size.unwrap_or(0) as usize).max(window_size);
+                let mut board = new_grid(GridStatus::NOT, board_size, board_size);
+                if let Some(pattern) = &pattern {
+                    pattern::place_centered(&mut board, pattern);
+                }
+                model
+                    .use_bitgrid(&board)
+                    .unwrap_or_else(|e| panic!("Cannot start the bit grid {e}"));
+            }
+        }
+        model
+    }
+
+    fn engine(&self) -> Engine {
+        match self.backend {
+            Backend::Grid => Engine::Grid,
+            Backend::Hashlife { .. } => Engine::Hashlife,
+            Backend::BitGrid(_) => Engine::BitGrid,
+            Backend::Sparse(_) => Engine::Sparse,
+        }
+    }
+
+    fn session(&self) -> Session {
+        let (step_exponent, board_size) = match &self.backend {
+            Backend::Hashlife { step_exponent, .. } => (*step_exponent, None),
+            Backend::BitGrid(bits) => (0, Some(bits.width())),
+            Backend::Grid | Backend::Sparse(_) => (0, None),
+        };
+        Session {
+            rule: self.rule.to_string(),
+            topology: self.topology,
+            engine: self.engine(),
+            step_exponent,
+            board_size,
+            generation: self.generation,
+            running: self.running,
+            speed: self.clock.speed,
+            camera: self.camera,
+            seed: self.seed,
+            gradient: self.gradient.to_string(),
+            trail: self.trail,
+            detect: self.detect,
+            grid_size: Some((self.grid.len(), self.grid[0].len())),
+            fit_cell_size: self.fit_cell_size,
+            cells: self.live_cells(),
+        }
+    }
+
+    // Picks up a saved session, with the engine and settings it was saved with.
+    fn from_session(session: Session) -> Result<Self, String> {
+        let mut model = Model {
+            rule: session.rule.parse()?,
+            topology: session.topology,
+            running: session.running,
+            seed: session.seed,
+            gradient: session.gradient.parse()?,
+            trail: session.trail,
+            detect: session.detect,
+            fit_cell_size: session.fit_cell_size,
+            ..Model::new(session.grid_size)
+        };
+        model.clock.set_speed(session.speed);
+        let (columns, rows) = (model.grid.len(), model.grid[0].len());
+        let empty = model.grid.clone();
+        match session.engine {
+            Engine::Grid => {}
+            Engine::Hashlife => model.use_hashlife(&empty, session.step_exponent)?,
+            Engine::Sparse => model.use_sparse(&empty)?,
+            Engine::BitGrid => {
+                let board_size = session.board_size.unwrap_or(0).max(columns).max(rows);
+                model.use_bitgrid(&new_grid(GridStatus::NOT, board_size, board_size))?;
+            }
+        }
+        for (x, y, status) in session.cells {
+            model.set_world_cell(x, y, status);
+        }
+        model.generation = session.generation;
+        if let Backend::Hashlife { universe, .. } = &mut model.backend {
+            universe.set_generation(session.generation);
+        }
+        model.camera = session.camera;
+        model.sync_viewport();
+        Ok(model)
+    }
+
+    // Live cells in the whole simulation, not only the window.
+    fn population(&self) -> u64 {
+        match &self.backend {
+            Backend::Grid => self
+                .grid
+                .iter()
+                .flatten()
+                .filter(|cell| **cell == GridStatus::LIFE)
+                .count() as u64,
+            Backend::Hashlife { universe, .. } => universe.population(),
+            Backend::BitGrid(bits) => bits.population(),
+            Backend::Sparse(universe) => universe.population(),
+        }
+    }
+
+    // Bounds of the live and dying cells, as (min_x, min_y, max_x, max_y).
+    // Grid and bit grid count from their bottom left cell, hashlife and sparse from their origin.
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        let to_i64 = |(x0, y0, x1, y1): (usize, usize, usize, usize)| {
+            (x0 as i64, y0 as i64, x1 as i64, y1 as i64)
+        };
+        match &self.backend {
+            Backend::Grid => pattern::bounding_box(&self.grid).map(to_i64),
+            Backend::Hashlife { universe, .. } => universe.bounding_box(),
+            Backend::BitGrid(bits) => bits.bounding_box().map(to_i64),
+            Backend::Sparse(universe) => universe.bounding_box(),
+        }
+    }
+
+    // Live and dying cells of the whole simulation, in the `bounding_box` coordinates.
+    fn live_cells(&self) -> Vec<(i64, i64, GridStatus)> {
+        match &self.backend {
+            Backend::Grid => pattern::cells(&self.grid),
+            Backend::Hashlife { universe, .. } => match universe.bounding_box() {
+                Some((x0, y0, x1, y1)) => universe
+                    .live_cells(x0, y0, x1 + 1, y1 + 1)
+                    .into_iter()
+                    .map(|(x, y)| (x, y, GridStatus::LIFE))
+                    .collect(),
+                None => vec![],
+            },
+            Backend::BitGrid(bits) => bits
+                .live_cells()
+                .into_iter()
+                .map(|(x, y)| (x as i64, y as i64, GridStatus::LIFE))
+                .collect(),
+            Backend::Sparse(universe) => universe.cells().collect(),
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let board = match &self.backend {
+            Backend::Grid => Board::Grid,
+            Backend::Hashlife { universe, .. } => Board::Hashlife(universe.root()),
+            Backend::BitGrid(bits) => Board::BitGrid(bits.clone()),
+            Backend::Sparse(universe) => Board::Sparse(universe.clone()),
+        };
+        Snapshot {
+            grid: self.grid.clone(),
+            board,
+            generation: self.generation,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.grid = snapshot.grid;
+        self.generation = snapshot.generation;
+        // The shapes seen and the samples after this generation belong to the abandoned future.
+        self.detector = Detector::default();
+        self.stats.truncate(snapshot.generation);
+        self.ages = None;
+        match (&mut self.backend, snapshot.board) {
+            (Backend::Hashlife { universe, .. }, Board::Hashlife(root)) => {
+                universe.set_root(root);
+                universe.set_generation(snapshot.generation);
+            }
+            (Backend::BitGrid(bits), Board::BitGrid(past)) => *bits = past,
+            (Backend::Sparse(universe), Board::Sparse(past)) => *universe = past,
+            _ => {}
+        }
+        // The camera may have moved since.
+        self.sync_viewport();
+    }
+
+    // Keeps the state from before an edit for undo.
+    // Skipped without a history to keep, like in headless runs.
+    fn remember_edit(&mut self) {
+        if self.edits.capacity() > 0 {
+            let snapshot = self.snapshot();
+            self.edits.record(snapshot);
+        }
+    }
+
+    // Keeps the state from before a generation for stepping back. Edits undone before it
+    // cannot be redone any more.
+    fn remember_generation(&mut self) {
+        self.edits.clear_future();
+        if self.generations.capacity() > 0 {
+            let snapshot = self.snapshot();
+            self.generations.record(snapshot);
+        }
+    }
+
+    // Generations stepped from at or after the edit belong to the timeline left behind.
+    fn undo(&mut self) {
+        if let Ok(snapshot) = self.edits.undo(self.snapshot()) {
+            let generation = snapshot.generation;
+            self.restore(snapshot);
+            self.generations.retain(|kept| kept.generation < generation);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Ok(snapshot) = self.edits.redo(self.snapshot()) {
+            let generation = snapshot.generation;
+            self.restore(snapshot);
+            self.generations.retain(|kept| kept.generation < generation);
+        }
+    }
+
+    // Goes back to the state from before the last generation. Edits made after it belong
+    // to the timeline left behind, the ones made at that generation came before the step.
+    fn step_back(&mut self) {
+        if let Ok(snapshot) = self.generations.undo(self.snapshot()) {
+            let generation = snapshot.generation;
+            self.restore(snapshot);
+            self.edits.retain(|kept| kept.generation <= generation);
+        }
+    }
+
+    // Sets both histories' capacity, nothing kept for 0.
+    fn set_history_len(&mut self, len: usize) {
+        self.edits.set_capacity(len);
+        self.generations.set_capacity(len);
+    }
+
+    // Starts painting `status` from the cell under `point`, along the drag or as a `shape`
+    // drawn on release. The whole drag is a single edit.
+    fn start_stroke(&mut self, point: Point2, status: GridStatus, shape: Option<Shape>) {
+        self.remember_edit();
+        // Edits start a new history.
+        self.detector = Detector::default();
+        let cell = self.world_cell_at(point);
+        self.stroke = Some(Stroke {
+            status,
+            shape,
+            start: cell,
+            end: cell,
+        });
+        if shape.is_none() {
+            self.set_world_cell(cell.0, cell.1, status);
+        }
+    }
+
+    // Follows the drag to `point`. Without a shape, paints a line from the last cell
+    // so a fast drag leaves no gaps.
+    fn continue_stroke(&mut self, point: Point2) {
+        let cell = self.world_cell_at(point);
+        let Some(stroke) = &mut self.stroke else {
+            return;
+        };
+        let (last, status, shape) = (stroke.end, stroke.status, stroke.shape);
+        stroke.end = cell;
+        if shape.is_none() {
+            for (x, y) in drawing::line(last, cell) {
+                self.set_world_cell(x, y, status);
+            }
+        }
+    }
+
+    fn end_stroke(&mut self) {
+        let Some(stroke) = self.stroke.take() else {
+            return;
+        };
+        if let Some(shape) = stroke.shape {
+            for (x, y) in shape.cells(stroke.start, stroke.end) {
+                self.set_world_cell(x, y, stroke.status);
+            }
+        }
+    }
+
+    // Window grid cells the shape being dragged would cover.
+    fn shape_cells(&self) -> Vec<(usize, usize)> {
+        let Some(Stroke {
+            shape: Some(shape),
+            start,
+            end,
+            ..
+        }) = &self.stroke
+        else {
+            return vec![];
+        };
+        shape
+            .cells(*start, *end)
+            .into_iter()
+            .filter_map(|(x, y)| {
+                let x = usize::try_from(x - self.origin.0).ok()?;
+                let y = usize::try_from(y - self.origin.1).ok()?;
+                (x < self.grid.len() && y < self.grid[0].len()).then_some((x, y))
+            })
+            .collect()
+    }
+
+    // Copies the stamp onto the grid, centred on the cell under `point`.
+    fn stamp_mouse_grid(&mut self, point: Point2) {
+        let Some(stamp) = &self.stamp else {
+            return;
+        };
+        let cells = self.stamp_cells(stamp, point);
+        self.remember_edit();
+        for (x, y) in cells {
+            self.set_cell(x, y, GridStatus::LIFE);
+        }
+    }
+
+    // Window grid cells the stamp covers with `point` under its centre.
+    // They wrap around like the topology on the grid engine, or are clipped.
+    fn stamp_cells(&self, stamp: &Stamp, point: Point2) -> Vec<(usize, usize)> {
+        let Some((x, y)) = self.cell_at(point) else {
+            return vec![];
+        };
+        let (width, height) = (self.grid.len(), self.grid[0].len());
+        let topology = match self.backend {
+            Backend::Grid => self.topology,
+            _ => Topology::Bounded,
+        };
+        stamp
+            .cells_at(x, y)
+            .into_iter()
+            .filter_map(|(x, y)| topology.wrap(x, y, width, height))
+            .collect()
+    }
+
+    // World cell under a window point, in sight or not.
+    fn world_cell_at(&self, point: Point2) -> (i64, i64) {
+        let (x, y) = self.camera.world_point(point);
+        self.rule.tiling().cell_at(x, y)
+    }
+
+    // Window grid cell under a window point, if there is one.
+    fn cell_at(&self, point: Point2) -> Option<(usize, usize)> {
+        let (x, y) = self.world_cell_at(point);
+        let x = usize::try_from(x - self.origin.0).ok()?;
+        let y = usize::try_from(y - self.origin.1).ok()?;
+        (x < self.grid.len() && y < self.grid[0].len()).then_some((x, y))
+    }
+
+    // Follows the window to its new `size` in pixels. A grid that fills the window gains
+    // or loses columns and rows evenly on both sides, so the cells stay where they are
+    // on screen. The other engines show more or less of their world.
+    fn resize(&mut self, size: Vec2) {
+        self.window = (size.x, size.y);
+        if let (Backend::Grid, Some(cell_size)) = (&self.backend, self.fit_cell_size) {
+            let columns = ((size.x / cell_size) as usize).max(1);
+            let rows = ((size.y / cell_size) as usize).max(1);
+            self.resize_grid(columns, rows);
+        }
+        self.sync_viewport();
+    }
+
+    // Grows or shrinks the grid engine's grid to `columns` x `rows` around its middle.
+    // Cells past the new edges are dropped.
+    fn resize_grid(&mut self, columns: usize, rows: usize) {
+        let (width, height) = (self.grid.len(), self.grid[0].len());
+        if (width, height) == (columns, rows) {
+            return;
+        }
+        // Even shifts keep hexagons and triangles pointing the same way.
+        let shift = |old: usize, new: usize| (new as i64 - old as i64) / 4 * 2;
+        let (dx, dy) = (shift(width, columns), shift(height, rows));
+        self.grid = shift_grid(&self.grid, columns, rows, (dx, dy));
+        // Undo and step back bring back grids of the new size, where the cells now are.
+        for snapshot in self.edits.states_mut().chain(self.generations.states_mut()) {
+            snapshot.grid = shift_grid(&snapshot.grid, columns, rows, (dx, dy));
+        }
+        self.camera.centre.0 += dx as f32;
+        self.camera.centre.1 += dy as f32;
+        // Ages and shapes seen are kept by grid position, which just moved.
+        self.ages = None;
+        self.detector = Detector::default();
+    }
+
+    fn pan(&mut self, delta: Vec2) {
+        self.camera.pan(delta);
+        self.sync_viewport();
+    }
+
+    fn zoom(&mut self, factor: f32, anchor: Point2) {
+        self.camera.zoom(factor, anchor);
+        self.sync_viewport();
+    }
+
+    // Empties the whole simulation, not only the window.
+    fn clear(&mut self) {
+        self.remember_edit();
+        self.clear_cells();
+    }
+
+    fn clear_cells(&mut self) {
+        for col in self.grid.iter_mut() {
+            col.fill(GridStatus::NOT);
+        }
+        self.detector = Detector::default();
+        self.ages = None;
+        match &mut self.backend {
+            Backend::Grid => {}
+            // Past roots still use the arena.
+            Backend::Hashlife { universe, .. } => universe.clear(),
+            Backend::BitGrid(bits) => **bits = BitGrid::new(bits.width(), bits.height()),
+            Backend::Sparse(universe) => *universe = Sparse::new(self.rule).unwrap(),
+        }
+    }
+
+    // Replaces everything with the pattern in the middle of the window.
+    fn replace_with(&mut self, pattern: &pattern::Pattern) {
+        self.remember_edit();
+        self.clear_cells();
+        let mut grid = self.grid.clone();
+        pattern::place_centered(&mut grid, pattern);
+        for (x, col) in grid.into_iter().enumerate() {
+            for (y, cell) in col.into_iter().enumerate() {
+                if cell != GridStatus::NOT {
+                    self.set_cell(x, y, cell);
+                }
+            }
+        }
+    }
+
+    // Swaps live and dead cells in the window. The unbounded engines cannot invert
+    // the infinite plane, so only the part in sight changes.
+    fn invert(&mut self) {
+        self.remember_edit();
+        for x in 0..self.grid.len() {
+            for y in 0..self.grid[x].len() {
+                self.set_cell(x, y, self.grid[x][y].toggle());
+            }
+        }
+    }
+
+    // Runs as many steps as the clock asks for in `elapsed` time.
+    fn advance(&mut self, elapsed: Duration) {
+        for _ in 0..self.clock.steps(elapsed) {
+            self.step();
+        }
+    }
+
+    // Sets a window grid cell and the cell under it in the backend.
+    fn set_cell(&mut self, x: usize, y: usize, status: GridStatus) {
+        // Edits start a new history.
+        self.detector = Detector::default();
+        self.set_world_cell(self.origin.0 + x as i64, self.origin.1 + y as i64, status);
+    }
+
+    // Sets a cell of the simulation, and of the window grid when it shows it.
+    fn set_world_cell(&mut self, world_x: i64, world_y: i64, status: GridStatus) {
+        let (x, y) = (world_x - self.origin.0, world_y - self.origin.1);
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            if x < self.grid.len() && y < self.grid[0].len() {
+                self.grid[x][y] = status;
+            }
+        }
+
+        let alive = status == GridStatus::LIFE;
+        if let Some(ages) = &mut self.ages {
+            ages.set(world_x, world_y, alive);
+        }
+        match &mut self.backend {
+            Backend::Grid => {}
+            Backend::Hashlife { universe, .. } => {
+                // Setting a cell rebuilds its path to the root, skip it when nothing changes.
+                if universe.get_cell(world_x, world_y) != alive {
+                    universe.set_cell(world_x, world_y, alive);
+                }
+            }
+            Backend::BitGrid(bits) => {
+                if let Some((x, y)) = on_board(bits, world_x, world_y) {
+                    bits.set(x, y, alive);
+                }
+            }
+            Backend::Sparse(universe) => universe.set(world_x, world_y, status),
         }
     }
-    fn toggle_mouse_grid(&mut self, point: Point2) {
-        let x = constraint_to_grid(point.x);
-        let y = constraint_to_grid(point.y);
-        self.grid[x][y] = self.grid[x][y].toggle();
+
+    // The live `cells` centred on the world origin, like `pattern::place_centered`
+    // would centre them on the window grid.
+    fn centred_cells(&self, cells: &Grid) -> Vec<(i64, i64, GridStatus)> {
+        let offset_x = (self.grid.len() as i64 - cells.len() as i64) / 2;
+        let offset_y = (self.grid[0].len() as i64 - cells[0].len() as i64) / 2;
+        let half_width = self.grid.len() as i64 / 2;
+        let half_height = self.grid[0].len() as i64 / 2;
+        let mut centred = vec![];
+        for (x, col) in cells.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                if *cell != GridStatus::NOT {
+                    let x = x as i64 + offset_x - half_width;
+                    let y = y as i64 + offset_y - half_height;
+                    centred.push((x, y, *cell));
+                }
+            }
+        }
+        centred
+    }
+
+    // Switches to hashlife, seeded with the live `cells` in the middle of the window.
+    fn use_hashlife(&mut self, cells: &Grid, step_exponent: u8) -> Result<(), String> {
+        if step_exponent > MAX_STEP_EXPONENT {
+            return Err(format!(
+                "step exponent {step_exponent} is too large, expected 0 to {MAX_STEP_EXPONENT}"
+            ));
+        }
+        let mut universe = Universe::new(self.rule)?;
+        for (x, y, _) in self.centred_cells(cells) {
+            universe.set_cell(x, y, true);
+        }
+        self.backend = Backend::Hashlife {
+            universe: Box::new(universe),
+            step_exponent,
+        };
+        // Past states of another universe have no roots in this one.
+        self.edits = History::new(self.edits.capacity());
+        self.generations = History::new(self.generations.capacity());
+        self.camera.centre = (0.0, 0.0);
+        self.sync_viewport();
+        Ok(())
+    }
+
+    // Switches to the sparse universe, seeded with the `cells` in the middle of the window.
+    fn use_sparse(&mut self, cells: &Grid) -> Result<(), String> {
+        let mut universe = Sparse::new(self.rule)?;
+        for (x, y, status) in self.centred_cells(cells) {
+            universe.set(x, y, status);
+        }
+        self.backend = Backend::Sparse(universe);
+        self.camera.centre = (0.0, 0.0);
+        self.sync_viewport();
+        Ok(())
+    }
+
+    // Switches to the bit grid, with `board` as its whole board.
+    fn use_bitgrid(&mut self, board: &Grid) -> Result<(), String> {
+        if !BitGrid::supports(self.topology) {
+            return Err(format!("the bit grid cannot run on a {:?}", self.topology));
+        }
+        self.backend = Backend::BitGrid(Box::new(BitGrid::from_grid(board)));
+        // Looking at the middle of the board.
+        self.camera.centre = (board.len() as f32 / 2.0, board[0].len() as f32 / 2.0);
+        let board_bytes = (board.len() * board[0].len() / 8).max(1);
+        self.set_history_len((HISTORY_BYTES / board_bytes).clamp(1, HISTORY_LEN));
+        self.sync_viewport();
+        Ok(())
+    }
+
+    // Fills the grid with the part of the universe or board the camera sees.
+    fn sync_viewport(&mut self) {
+        if let Backend::Grid = self.backend {
+            return;
+        }
+        let (x0, y0, x1, y1) = self.camera.visible(self.window.0, self.window.1);
+        let (width, height) = ((x1 - x0 + 1) as usize, (y1 - y0 + 1) as usize);
+        let mut grid = vec![vec![GridStatus::NOT; height]; width];
+        match &self.backend {
+            Backend::Grid => {}
+            Backend::Hashlife { universe, .. } => {
+                for (x, y) in universe.live_cells(x0, y0, x1 + 1, y1 + 1) {
+                    grid[(x - x0) as usize][(y - y0) as usize] = GridStatus::LIFE;
+                }
+            }
+            Backend::BitGrid(bits) => {
+                for (x, col) in grid.iter_mut().enumerate() {
+                    for (y, cell) in col.iter_mut().enumerate() {
+                        if let Some((x, y)) = on_board(bits, x0 + x as i64, y0 + y as i64) {
+                            if bits.get(x, y) {
+                                *cell = GridStatus::LIFE;
+                            }
+                        }
+                    }
+                }
+            }
+            Backend::Sparse(universe) => {
+                // Looking up the visible cells costs the same whatever the population.
+                for (x, col) in grid.iter_mut().enumerate() {
+                    for (y, cell) in col.iter_mut().enumerate() {
+                        *cell = universe.get(x0 + x as i64, y0 + y as i64);
+                    }
+                }
+            }
+        }
+        let moved =
+            (x0, y0) != self.origin || (width, height) != (self.grid.len(), self.grid[0].len());
+        self.grid = grid;
+        self.origin = (x0, y0);
+        if let (true, Some(ages)) = (moved, &mut self.ages) {
+            ages.show(self.origin, &self.grid);
+        }
+    }
+
+    // Advances one generation, or 2^k with hashlife, and looks for a repeating shape.
+    // Statistics are recorded from the first step on.
+    fn step(&mut self) {
+        self.remember_generation();
+        if self.detector.is_empty() {
+            self.detect();
+        }
+        if self.stats.is_empty() {
+            self.record_stats(Some((0, 0)));
+        }
+        // Ages follow the cells of the window that changed.
+        if self.trail == 0 && self.gradient.is_flat() {
+            self.ages = None;
+        } else if self.ages.is_none() {
+            self.ages = Some(Ages::new(self.generation, self.origin, &self.grid));
+        }
+        let before = self.ages.is_some().then(|| self.grid.clone());
+        let changes = self.next_generation();
+        self.detect();
+        self.record_stats(changes);
+        if let (Some(ages), Some(before)) = (&mut self.ages, before) {
+            let (x0, y0) = self.origin;
+            let changes = changed_cells(&before, &self.grid)
+                .map(|(x, y, born)| (x0 + x as i64, y0 + y as i64, born));
+            ages.update(self.generation, changes, self.trail);
+        }
+    }
+
+    // Shows the current generation to the detector, while detection is on and the pattern
+    // may still turn out to be something else.
+    fn detect(&mut self) {
+        let unbounded = matches!(self.backend, Backend::Hashlife { .. } | Backend::Sparse(_));
+        let settled = match self.detector.result() {
+            // A spaceship on a bounded grid can still hit the edge.
+            Some(Classification::Spaceship { .. }) => unbounded,
+            // The same cells in the same place go through the same generations again.
+            result => result.is_some(),
+        };
+        if !self.detect || settled {
+            return;
+        }
+        let cells = self.live_cells();
+        self.detector.observe(self.generation, &cells);
+        if let Backend::Hashlife {
+            universe,
+            step_exponent,
+        } = &mut self.backend
+        {
+            if *step_exponent > 0 {
+                self.detector.refine(1 << *step_exponent, |generations| {
+                    universe
+                        .cells_after(generations)
+                        .into_iter()
+                        .map(|(x, y)| (x, y, GridStatus::LIFE))
+                        .collect()
+                });
+            }
+        }
+    }
+
+    // Samples the current generation, with the births and deaths since the last one
+    // if the engine knows them.
+    fn record_stats(&mut self, changes: Option<(u64, u64)>) {
+        let (population, bounding_box) = (self.population(), self.bounding_box());
+        self.stats
+            .record(self.generation, population, changes, bounding_box);
+    }
+
+    // Turns detection on or off, starting over from the current generation.
+    fn toggle_detection(&mut self) {
+        self.detect = !self.detect;
+        self.detector = Detector::default();
+    }
+
+    // Colour of a window grid cell, by age for live cells and fading for ghosts.
+    fn cell_color(&self, x: usize, y: usize) -> Rgb {
+        let (world_x, world_y) = (self.origin.0 + x as i64, self.origin.1 + y as i64);
+        let color = match self.grid[x][y] {
+            GridStatus::LIFE => {
+                let age = self
+                    .ages
+                    .as_ref()
+                    .and_then(|ages| ages.age(world_x, world_y));
+                return self.gradient.at(age.unwrap_or(1));
+            }
+            GridStatus::NOT => match self
+                .ages
+                .as_ref()
+                .and_then(|ages| ages.ghost(world_x, world_y))
+            {
+                Some(ghost) => return self.gradient.ghost(ghost, self.trail),
+                None => BLACK,
+            },
+            dying => dying.color(),
+        };
+        rgb(
+            color.red as f32 / 255.0,
+            color.green as f32 / 255.0,
+            color.blue as f32 / 255.0,
+        )
+    }
+
+    // Advances the backend, returning how many cells were born and how many died, except
+    // with hashlife where it would take going through every cell.
+    fn next_generation(&mut self) -> Option<(u64, u64)> {
+        match &mut self.backend {
+            Backend::Grid => {}
+            Backend::Hashlife {
+                universe,
+                step_exponent,
+            } => {
+                universe.step(*step_exponent);
+                if universe.is_crowded() {
+                    let roots = self
+                        .edits
+                        .states_mut()
+                        .chain(self.generations.states_mut())
+                        .filter_map(|snapshot| match &mut snapshot.board {
+                            Board::Hashlife(root) => Some(root),
+                            _ => None,
+                        });
+                    universe.collect_garbage(roots);
+                }
+                self.generation = universe.generation();
+                self.sync_viewport();
+                return None;
+            }
+            Backend::BitGrid(bits) => {
+                let changes = bits.step(&self.rule, self.topology);
+                self.generation += 1;
+                self.sync_viewport();
+                return Some(changes);
+            }
+            Backend::Sparse(universe) => {
+                let changes = universe.step(&self.rule);
+                self.generation += 1;
+                self.sync_viewport();
+                return Some(changes);
+            }
+        }
+        self.generation += 1;
+
+        let counts = self
+            .rule
+            .larger()
+            .map(|larger| larger.counts(&self.grid, self.topology));
+        // Every column only reads the current grid and writes its own column of the next one,
+        // so bands of columns are computed in parallel with the same result in any order.
+        let height = self.grid[0].len();
+        let new_grid: Grid = (0..self.grid.len())
+            .into_par_iter()
+            .with_min_len(COLUMNS_PER_TASK)
+            .map(|x| {
+                (0..height)
+                    .map(|y| self.next_cell(x, y, counts.as_deref()))
+                    .collect()
+            })
+            .collect();
+
+        let changes = changed_cells(&self.grid, &new_grid).fold(
+            (0, 0),
+            |(births, deaths), cell| match cell.2 {
+                true => (births + 1, deaths),
+                false => (births, deaths + 1),
+            },
+        );
+        self.grid = new_grid;
+        Some(changes)
+    }
+
+    // Next state of the window grid cell (x, y), from the Larger than Life `counts` if any.
+    fn next_cell(&self, x: usize, y: usize, counts: Option<&[Vec<usize>]>) -> GridStatus {
+        if let Some(counts) = counts {
+            return self.grid[x][y].next(counts[x][y], &self.rule);
+        }
+        match self.rule.tiling() {
+            Tiling::Square => {
+                let mut neighbors = self.neighbors(x, y);
+                self.grid[x][y].game_rules(&mut neighbors, &self.rule)
+            }
+            tiling => {
+                let total = self.tiled_neighbors(tiling, x, y);
+                self.grid[x][y].next(total, &self.rule)
+            }
+        }
     }
 
     // Get the context of neighbors for xx (bb).  then
@@ -165,26 +1330,39 @@ impl Model {
     fn neighbors(&self, x: usize, y: usize) -> [u8; 9] {
         let mut neighbors = [0; 9];
         neighbors.fill(0);
-        let size = self.grid.len() as isize;
+        let width = self.grid.len();
+        let height = self.grid[0].len();
 
         for i in 0..3 {
             let point_x: isize = (x + i) as isize - 1;
             let neighbor_i = i * 3;
             for j in 0..3 {
                 let point_y: isize = (y + j) as isize - 1;
-                let poss: u8;
-                let cell_outside_grid =
-                    point_x < 0 || point_x >= size || point_y < 0 || point_y >= size;
-                if cell_outside_grid {
-                    poss = GridStatus::NOT.grid_value();
-                } else {
-                    poss = self.grid[point_x as usize][point_y as usize].grid_value();
-                }
+                // Cells outside the grid are dead, unless the topology wraps them around.
+                let poss = match self.topology.wrap(point_x, point_y, width, height) {
+                    Some((x, y)) => self.grid[x][y].grid_value(),
+                    None => GridStatus::NOT.grid_value(),
+                };
                 neighbors[neighbor_i + j] = poss;
             }
         }
         return neighbors;
     }
+
+    // Count of live neighbours of (x, y) on hexagonal or triangular cells.
+    fn tiled_neighbors(&self, tiling: Tiling, x: usize, y: usize) -> usize {
+        let width = self.grid.len();
+        let height = self.grid[0].len();
+        tiling
+            .neighbor_offsets(x as isize, y as isize)
+            .iter()
+            .filter_map(|(dx, dy)| {
+                self.topology
+                    .wrap(x as isize + dx, y as isize + dy, width, height)
+            })
+            .map(|(x, y)| self.grid[x][y].grid_value() as usize)
+            .sum()
+    }
 }
 
 #[cfg(test)]
@@ -231,7 +1409,7 @@ mod test_model_operations {
                     GridStatus::LIFE,
                 ],
             ],
-            running: false,
+            ..Model::new(None)
         };
 
         println!("First 2, 2");
@@ -253,39 +1431,527 @@ mod test_model_operations {
         let neigh = model_calc.neighbors(5, 5);
         let model_expected = [1, 0, 0, 0, 0, 0, 0, 0, 0];
         assert_eq!(neigh, model_expected);
+
+        // 0, 0 without the top left corner.
+        let mut model_calc = model_calc;
+        model_calc.grid[0][4] = GridStatus::NOT;
+        let topologies = [
+            (Topology::Bounded, [0, 0, 0, 0, 0, 0, 0, 0, 1]),
+            (Topology::Torus, [1, 0, 0, 0, 0, 0, 0, 0, 1]),
+            (Topology::KleinBottle, [0, 0, 0, 1, 0, 0, 0, 0, 1]),
+            (Topology::ProjectivePlane, [0, 1, 0, 1, 0, 0, 0, 0, 1]),
+        ];
+        for (topology, model_expected) in topologies {
+            model_calc.topology = topology;
+            let neigh = model_calc.neighbors(0, 0);
+            assert_eq!(neigh, model_expected, "{topology:?}");
+        }
+
+        // On the torus 4, 2 wraps to the left column.
+        model_calc.topology = Topology::Torus;
+        let neigh = model_calc.neighbors(4, 2);
+        let model_expected = [1, 1, 1, 0, 0, 0, 0, 0, 0];
+        assert_eq!(neigh, model_expected);
+    }
+
+    #[test]
+    fn keyboard_actions() {
+        let mut model = Model::new(None);
+        model.grid[5][4..7].fill(GridStatus::LIFE);
+
+        // Ten steps per second: two steps in a quarter second, the half step is kept.
+        model.clock.speed = 10.0;
+        model.advance(Duration::from_millis(250));
+        assert_eq!(model.generation, 2);
+        model.advance(Duration::from_millis(50));
+        assert_eq!(model.generation, 3);
+        model.clock.change_speed(false);
+        assert_eq!(model.clock.speed, 5.0);
+
+        let cells = model.grid.len() * model.grid[0].len();
+        model.invert();
+        assert_eq!(model.population() as usize, cells - 3);
+        model.clear();
+        assert_eq!(model.population(), 0);
+        model.undo();
+        model.undo();
+        assert_eq!(model.population(), 3);
     }
+
+    #[test]
+    fn viewport_follows_the_camera() {
+        let mut model = Model::new(None);
+        let mut blinker = model.grid.clone();
+        blinker[5][4..7].fill(GridStatus::LIFE);
+        model.use_sparse(&blinker).unwrap();
+        assert_eq!(model.grid, blinker);
+        assert_eq!(model.cell_at(pt2(-245.0, -255.0)), Some((5, 4)));
+
+        // Ten cells to the right, the blinker shows ten columns further.
+        model.pan(vec2(100.0, 0.0));
+        assert_eq!(model.grid[15][5], GridStatus::LIFE);
+        model.start_stroke(pt2(-145.0, -255.0), GridStatus::NOT, None);
+        model.end_stroke();
+        assert_eq!(model.grid[15][4], GridStatus::NOT);
+        assert_eq!(model.population(), 2);
+
+        // Twice the cell size, half the cells on each side.
+        model.zoom(2.0, pt2(0.0, 0.0));
+        assert_eq!((model.grid.len(), model.grid[0].len()), (30, 30));
+        assert_eq!(model.population(), 2);
+    }
+
+    #[test]
+    fn painting_and_shapes() {
+        let mut model = Model::new(None);
+        // Window points over the middle of grid cells.
+        let camera = model.camera;
+        let point = |x: usize, y: usize| camera.screen_at(x as i64, y as i64) + 5.0;
+
+        // A fast drag paints every cell on the way, as a single edit.
+        model.start_stroke(point(2, 2), GridStatus::LIFE, None);
+        model.continue_stroke(point(6, 4));
+        model.continue_stroke(point(6, 8));
+        model.end_stroke();
+        assert_eq!(model.population(), 5 + 4);
+        assert_eq!(model.grid[4][3], GridStatus::LIFE);
+        model.start_stroke(point(6, 8), GridStatus::NOT, None);
+        model.continue_stroke(point(6, 6));
+        model.end_stroke();
+        assert_eq!(model.population(), 9 - 3);
+        model.undo();
+        assert_eq!(model.population(), 9);
+        model.undo();
+        assert_eq!(model.population(), 0);
+
+        // Shapes show where they would go, and land on release.
+        model.shape = Shape::Rectangle;
+        model.start_stroke(point(10, 10), GridStatus::LIFE, Some(model.shape));
+        model.continue_stroke(point(13, 12));
+        assert_eq!(model.shape_cells().len(), 10);
+        assert_eq!(model.population(), 0);
+        model.end_stroke();
+        assert_eq!(model.population(), 10);
+        assert_eq!(model.grid[11][11], GridStatus::NOT);
+        assert!(model.shape_cells().is_empty());
+
+        model.start_stroke(point(9, 11), GridStatus::NOT, Some(Shape::Line));
+        model.continue_stroke(point(14, 11));
+        model.end_stroke();
+        // The line crossed the two sides of the hollow rectangle.
+        assert_eq!(model.population(), 8);
+        assert_eq!(model.grid[10][11], GridStatus::NOT);
+        assert_eq!(model.grid[10][10], GridStatus::LIFE);
+    }
+
+    #[test]
+    fn window_sized_grids() {
+        let mut model = Model {
+            window: (800.0, 300.0),
+            ..Model::new(Some((80, 30)))
+        };
+        assert_eq!(model.cell_at(pt2(-395.0, -145.0)), Some((0, 0)));
+        assert_eq!(model.cell_at(pt2(395.0, 145.0)), Some((79, 29)));
+        assert_eq!(model.cell_at(pt2(0.0, 200.0)), None);
+
+        // The blinker stays put on screen as the grid grows and shrinks around it.
+        model.grid[40][14..17].fill(GridStatus::LIFE);
+        let on_screen = model.camera.screen_at(40, 15);
+        model.resize(vec2(1000.0, 500.0));
+        assert_eq!((model.grid.len(), model.grid[0].len()), (100, 50));
+        assert_eq!(model.grid[50][24..27], [GridStatus::LIFE; 3]);
+        assert_eq!(model.camera.screen_at(50, 25), on_screen);
+        model.resize(vec2(200.0, 100.0));
+        assert_eq!((model.grid.len(), model.grid[0].len()), (20, 10));
+        assert_eq!(model.grid[10][4..7], [GridStatus::LIFE; 3]);
+        assert_eq!(model.camera.screen_at(10, 5), on_screen);
+        model.step();
+        assert_eq!(model.population(), 3);
+
+        // Stepping back brings the blinker back in the resized grid, where it now is.
+        model.resize(vec2(400.0, 200.0));
+        model.step_back();
+        assert_eq!((model.grid.len(), model.grid[0].len()), (40, 20));
+        assert_eq!(model.grid[20][8..11], [GridStatus::LIFE; 3]);
+
+        // A grid of fixed --columns and --rows keeps its size.
+        model.fit_cell_size = None;
+        model.resize(vec2(600.0, 600.0));
+        assert_eq!((model.grid.len(), model.grid[0].len()), (40, 20));
+    }
+
+    #[test]
+    fn hexagonal_cells() {
+        let mut model = Model::new(None);
+        model.rule = "B6/SH".parse().unwrap();
+        // The six neighbours of (10, 10), on an even row.
+        for (dx, dy) in model.rule.tiling().neighbor_offsets(10, 10) {
+            model.grid[(10 + dx) as usize][(10 + dy) as usize] = GridStatus::LIFE;
+        }
+        model.step();
+        assert_eq!(model.grid[10][10], GridStatus::LIFE);
+        assert_eq!(model.population(), 1);
+
+        // Odd rows sit half a cell to the right.
+        let cell = |x: f32, y: f32| model.camera.screen_point((x, y));
+        assert_eq!(model.cell_at(cell(10.9, 11.5)), Some((10, 11)));
+        assert_eq!(model.cell_at(cell(10.9, 10.5)), Some((10, 10)));
+    }
+
+    #[test]
+    fn ages_colour_the_cells() {
+        let mut model = Model {
+            gradient: "#ffffff,#000000".parse().unwrap(),
+            trail: 1,
+            ..Model::new(None)
+        };
+        model.grid[5][4..7].fill(GridStatus::LIFE);
+        model.step();
+        // The ends of the standing blinker died on the first step.
+        assert_eq!(model.cell_color(5, 4), model.gradient.ghost((1, 1), 1));
+        model.step();
+        // The middle of the blinker has been alive all along, its ends were just born.
+        assert_eq!(model.cell_color(5, 5), model.gradient.at(3));
+        assert_eq!(model.cell_color(5, 4), rgb(1.0, 1.0, 1.0));
+        // The lying ends died last step.
+        assert_eq!(model.cell_color(4, 5), model.gradient.ghost((1, 1), 1));
+        assert_eq!(model.cell_color(0, 0), rgb(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let cells = |model: &Model| {
+            let mut cells = model.live_cells();
+            cells.sort_by_key(|(x, y, _)| (*x, *y));
+            cells
+        };
+        for engine in [
+            Engine::Grid,
+            Engine::Hashlife,
+            Engine::BitGrid,
+            Engine::Sparse,
+        ] {
+            let mut model = Model::new(None);
+            let mut glider = model.grid.clone();
+            for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+                glider[x][y] = GridStatus::LIFE;
+            }
+            match engine {
+                Engine::Grid => model.grid = glider,
+                Engine::Hashlife => model.use_hashlife(&glider, 0).unwrap(),
+                Engine::BitGrid => model.use_bitgrid(&glider).unwrap(),
+                Engine::Sparse => model.use_sparse(&glider).unwrap(),
+            }
+            model.trail = 3;
+            model.pan(vec2(30.0, -20.0));
+            for _ in 0..5 {
+                model.step();
+            }
+
+            let json = serde_json::to_string(&model.session()).unwrap();
+            let mut loaded = Model::from_session(serde_json::from_str(&json).unwrap()).unwrap();
+            assert_eq!(loaded.engine(), engine);
+            assert_eq!((loaded.generation, loaded.trail), (5, 3));
+            assert_eq!(loaded.camera, model.camera);
+            assert_eq!(loaded.grid, model.grid, "{engine:?}");
+            assert_eq!(cells(&loaded), cells(&model), "{engine:?}");
+
+            model.step();
+            loaded.step();
+            assert_eq!(cells(&loaded), cells(&model), "{engine:?}");
+        }
+
+        // A step exponent hashlife cannot take is rejected rather than overflowing.
+        let mut model = Model::new(None);
+        let empty = model.grid.clone();
+        model.use_hashlife(&empty, MAX_STEP_EXPONENT).unwrap();
+        let mut session = model.session();
+        session.step_exponent = MAX_STEP_EXPONENT + 1;
+        assert!(Model::from_session(session).is_err());
+
+        // A board smaller than a byte still gets its history.
+        let mut model = Model::new(Some((2, 2)));
+        model.use_bitgrid(&new_grid(GridStatus::NOT, 2, 2)).unwrap();
+        assert_eq!(model.generations.capacity(), HISTORY_LEN);
+    }
+
+    #[test]
+    fn statistics_by_engine() {
+        for engine in [
+            Engine::Grid,
+            Engine::Hashlife,
+            Engine::BitGrid,
+            Engine::Sparse,
+        ] {
+            let mut model = Model::new(None);
+            let mut blinker = model.grid.clone();
+            blinker[5][4..7].fill(GridStatus::LIFE);
+            match engine {
+                Engine::Grid => model.grid = blinker,
+                Engine::Hashlife => model.use_hashlife(&blinker, 0).unwrap(),
+                Engine::BitGrid => model.use_bitgrid(&blinker).unwrap(),
+                Engine::Sparse => model.use_sparse(&blinker).unwrap(),
+            }
+            model.step();
+
+            let samples = model.stats.samples();
+            assert_eq!(samples.len(), 2);
+            assert_eq!((samples[0].births, samples[0].deaths), (Some(0), Some(0)));
+            let sample = &samples[1];
+            assert_eq!(sample.population, 3, "{engine:?}");
+            assert_eq!(sample.density, 1.0, "{engine:?}");
+            let changes = match engine {
+                Engine::Hashlife => (None, None),
+                _ => (Some(2), Some(2)),
+            };
+            assert_eq!((sample.births, sample.deaths), changes, "{engine:?}");
+        }
+    }
+
+    // The loop the grid engine stepped with before it ran in parallel.
+    fn sequential_generation(model: &Model) -> Grid {
+        let tiling = model.rule.tiling();
+        let counts = model
+            .rule
+            .larger()
+            .map(|larger| larger.counts(&model.grid, model.topology));
+        let mut new_grid = model.grid.clone();
+        for x in 0..model.grid.len() {
+            for y in 0..model.grid[x].len() {
+                if let Some(counts) = &counts {
+                    new_grid[x][y] = model.grid[x][y].next(counts[x][y], &model.rule);
+                    continue;
+                }
+                new_grid[x][y] = match tiling {
+                    Tiling::Square => {
+                        let mut neighbors = model.neighbors(x, y);
+                        model.grid[x][y].game_rules(&mut neighbors, &model.rule)
+                    }
+                    _ => {
+                        let total = model.tiled_neighbors(tiling, x, y);
+                        model.grid[x][y].next(total, &model.rule)
+                    }
+                };
+            }
+        }
+        new_grid
+    }
+
+    #[test]
+    fn parallel_step_matches_sequential() {
+        // Ten times the window grid, and a band of columns too short to split.
+        for (width, height) in [(600, 600), (7, 30)] {
+            for rule in [Rule::CONWAY, Rule::STAR_WARS, "B2/S34H", Rule::BOSCO] {
+                let mut model = Model {
+                    rule: rule.parse().unwrap(),
+                    topology: Topology::Torus,
+                    ..Model::new(None)
+                };
+                let soup = soup::random_soup(width.max(height), 0.4, Symmetry::None, 5).unwrap();
+                model.grid = soup[..width]
+                    .iter()
+                    .map(|col| col[..height].to_vec())
+                    .collect();
+                let mut bits = BitGrid::from_grid(&model.grid);
+                for _ in 0..3 {
+                    let sequential = sequential_generation(&model);
+                    model.next_generation();
+                    assert_eq!(model.grid, sequential, "{rule} {width}x{height}");
+                    if rule == Rule::CONWAY {
+                        bits.step(&model.rule, model.topology);
+                        assert_eq!(model.grid, bits.to_grid(), "{width}x{height}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn undo_redo_and_step_back() {
+        let mut model = Model::new(None);
+        for y in 4..7 {
+            model.grid[5][y] = GridStatus::LIFE;
+        }
+        let standing = model.grid.clone();
+        model.step();
+        let lying = model.grid.clone();
+
+        // An edit is undone and redone on its own.
+        model.start_stroke(pt2(0.0, 0.0), GridStatus::LIFE, None);
+        model.end_stroke();
+        let edited = model.grid.clone();
+        model.undo();
+        assert_eq!(model.grid, lying);
+        model.redo();
+        assert_eq!(model.grid, edited);
+
+        // Stepping back undoes the edit and the generation before it.
+        model.step_back();
+        assert_eq!(model.grid, standing);
+        assert_eq!(model.generation, 0);
+        model.step_back();
+        assert_eq!(model.grid, standing);
+        // Nor can undo jump forward to the edit stepped back over.
+        model.undo();
+        assert_eq!(model.generation, 0);
+
+        // A stamp is a single edit.
+        model.stamp = Stamp::library().into_iter().next();
+        model.stamp_mouse_grid(pt2(0.0, 0.0));
+        assert_eq!(model.population(), 8);
+        model.undo();
+        assert_eq!(model.grid, standing);
+
+        // Running for longer than the history does not push edits out of it, and undo
+        // skips the generations back to before the edit.
+        model.start_stroke(pt2(0.0, 0.0), GridStatus::LIFE, None);
+        model.end_stroke();
+        for _ in 0..HISTORY_LEN + 10 {
+            model.step();
+        }
+        model.undo();
+        assert_eq!(model.grid, standing);
+        assert_eq!(model.generation, 0);
+        // The generations run after the edit are gone with it.
+        model.step_back();
+        assert_eq!(model.grid, standing);
+        assert_eq!(model.generation, 0);
+
+        // Hashlife goes back to the root it had.
+        model.use_hashlife(&standing, 0).unwrap();
+        model.step();
+        model.step();
+        model.step_back();
+        assert_eq!(model.grid, lying);
+        assert_eq!(model.generation, 1);
+        model.step();
+        assert_eq!(model.grid, standing);
+        assert_eq!(model.generation, 2);
+    }
+}
+
+fn soup_from_args(args: &Args, seed: u64) -> pattern::Pattern {
+    // The seed is always printed, so any soup can be regenerated.
+    eprintln!("Soup seed {seed}");
+    let size = args.soup_size as usize;
+    let cells = soup::random_soup(size, args.density, args.symmetry, seed)
+        .unwrap_or_else(|e| panic!("Cannot make a soup {e}"));
+    pattern::Pattern { cells, rule: None }
+}
+
+// Board cell at world coordinates, `None` past the edges.
+fn on_board(bits: &BitGrid, x: i64, y: i64) -> Option<(usize, usize)> {
+    let x = usize::try_from(x).ok().filter(|x| *x < bits.width())?;
+    let y = usize::try_from(y).ok().filter(|y| *y < bits.height())?;
+    Some((x, y))
+}
+
+pub fn new_grid<T: Clone + std::fmt::Debug>(
+    initial_values: T,
+    columns: usize,
+    rows: usize,
+) -> Vec<Vec<T>> {
+    let col: Vec<T> = vec![initial_values.clone(); rows];
+    vec![col; columns]
+}
+
+// Cells of the window grid that were born, (x, y, true), or died, (x, y, false),
+// between `before` and `after`.
+fn changed_cells<'a>(
+    before: &'a Grid,
+    after: &'a Grid,
+) -> impl Iterator<Item = (usize, usize, bool)> + 'a {
+    before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .flat_map(|(x, (before, after))| {
+            before
+                .iter()
+                .zip(after)
+                .enumerate()
+                .filter_map(move |(y, (before, after))| {
+                    match (*before == GridStatus::LIFE, *after == GridStatus::LIFE) {
+                        (false, true) => Some((x, y, true)),
+                        (true, false) => Some((x, y, false)),
+                        _ => None,
+                    }
+                })
+        })
 }
 
-fn new_square_grid<T: Clone + std::fmt::Debug>(initial_values: T, size: u32) -> Vec<Vec<T>> {
-    let row: Vec<T> = vec![initial_values.clone(); size as usize];
-    vec![row; size as usize]
+// Copy of `grid` as `columns` x `rows`, every cell moved by `(dx, dy)`. Cells past the
+// new edges are dropped.
+fn shift_grid(grid: &Grid, columns: usize, rows: usize, (dx, dy): (i64, i64)) -> Grid {
+    let mut shifted = new_grid(GridStatus::NOT, columns, rows);
+    for (x, col) in grid.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            let (Ok(x), Ok(y)) = (
+                usize::try_from(x as i64 + dx),
+                usize::try_from(y as i64 + dy),
+            ) else {
+                continue;
+            };
+            if x < columns && y < rows {
+                shifted[x][y] = *cell;
+            }
+        }
+    }
+    shifted
+}
+
+// Window size in pixels: --columns and --rows cells of --cell-size, or WIDTH x HEIGHT.
+fn window_size(args: &Args) -> (f32, f32) {
+    let cell_size = args.cell_size as f32;
+    (
+        args.columns
+            .map_or(WIDTH as f32, |columns| columns as f32 * cell_size),
+        args.rows
+            .map_or(HEIGHT as f32, |rows| rows as f32 * cell_size),
+    )
 }
 
 fn model(app: &App) -> Model {
+    let args = ARGS.get().unwrap();
+    let mut model = Model::from_args(args);
+    // The window takes the size of a loaded session's grid.
+    let (width, height) = match args.load {
+        Some(_) => {
+            let cell_size = model.fit_cell_size.unwrap_or(model.camera.cell_size);
+            (
+                model.grid.len() as f32 * cell_size,
+                model.grid[0].len() as f32 * cell_size,
+            )
+        }
+        None => window_size(args),
+    };
     app.new_window()
         .view(view)
-        .size(WIDTH, HEIGHT)
+        .size(width as u32, height as u32)
+        .resized(resized)
         .mouse_pressed(mouse_pressed)
+        .mouse_released(mouse_released)
+        .mouse_moved(mouse_moved)
+        .mouse_wheel(mouse_wheel)
+        .key_pressed(key_pressed)
         .build()
         .unwrap();
 
-    Model::new(None)
+    // The window may not have the size asked for, e.g. on a small screen.
+    model.resize(app.window_rect().wh());
+    model
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn resized(_app: &App, model: &mut Model, size: Vec2) {
+    model.resize(size);
+}
+
+fn update(_app: &App, model: &mut Model, update: Update) {
     if !model.running {
         return;
     }
 
-    let mut new_grid = new_square_grid(GridStatus::NOT, WIDTH / BLOCK_SIZE);
-    for x in 0..model.grid.len() {
-        for y in 0..model.grid[x].len() {
-            let mut neighbors = model.neighbors(x, y);
-            new_grid[x][y] = model.grid[x][y].game_rules(&mut neighbors);
-        }
-    }
-
-    model.grid = new_grid;
+    model.advance(update.since_last);
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
@@ -300,46 +1966,267 @@ fn view(app: &App, model: &Model, frame: Frame) {
     }
     draw.background().color(background_color);
 
-    let axis_in_origin: f32 = MIDDLE as f32;
-    let block_size = BLOCK_SIZE as f32;
+    let block_size = model.camera.cell_size;
+    let window = Rect::from_w_h(model.window.0, model.window.1);
+    // Rectangle centre of a window grid cell, `None` when it is out of sight.
+    let cell_centre = |x: usize, y: usize| {
+        let corner = model
+            .camera
+            .screen_at(model.origin.0 + x as i64, model.origin.1 + y as i64);
+        let centre = corner + vec2(block_size, block_size) / 2.0;
+        let half = block_size / 2.0;
+        let visible = centre.x + half > window.left()
+            && centre.x - half < window.right()
+            && centre.y + half > window.bottom()
+            && centre.y - half < window.top();
+        visible.then_some(centre)
+    };
+
+    let tiling = model.rule.tiling();
+    // Hexagons and triangles in window points, drawn as polygons.
+    let cell_outline = |x: usize, y: usize| {
+        tiling
+            .vertices(model.origin.0 + x as i64, model.origin.1 + y as i64)
+            .into_iter()
+            .map(|corner| model.camera.screen_point(corner))
+            .collect::<Vec<_>>()
+    };
 
     for x in 0..model.grid.len() {
         for y in 0..model.grid[x].len() {
-            let cell = &model.grid[x][y];
-            let the_pos = |xy: f32| (block_size * xy) - axis_in_origin;
-            let pos_x = the_pos(x as f32);
-            let pos_y = the_pos(y as f32);
+            let Some(centre) = cell_centre(x, y) else {
+                continue;
+            };
 
+            let color = model.cell_color(x, y);
+            if tiling != Tiling::Square {
+                draw.polygon()
+                    .color(color)
+                    .stroke(cell_stroke)
+                    .stroke_weight(1.0)
+                    .points(cell_outline(x, y));
+                continue;
+            }
             draw.rect()
-                .color(cell.color())
+                .color(color)
                 .stroke(cell_stroke)
                 .stroke_weight(1.0)
-                .x_y(pos_x, pos_y)
+                .xy(centre)
                 .w(block_size)
                 .h(block_size);
         }
     }
 
+    // Where the stamp or the shape being dragged would land.
+    let preview = match &model.stamp {
+        Some(stamp) => model.stamp_cells(stamp, app.mouse.position()),
+        None => model.shape_cells(),
+    };
+    for (x, y) in preview {
+        let Some(centre) = cell_centre(x, y) else {
+            continue;
+        };
+        if tiling != Tiling::Square {
+            draw.polygon()
+                .color(rgba(0.6, 0.9, 0.6, 0.5))
+                .points(cell_outline(x, y));
+            continue;
+        }
+        draw.rect()
+            .color(rgba(0.6, 0.9, 0.6, 0.5))
+            .xy(centre)
+            .w(block_size)
+            .h(block_size);
+    }
+
+    let classification = model
+        .detector
+        .result()
+        .map_or("".to_string(), |result| result.to_string());
+    let seed = model
+        .seed
+        .map_or("".to_string(), |seed| format!("\nseed {seed}"));
+    let hud = format!(
+        "generation {}\npopulation {}\nspeed {} steps/s{seed}\n{classification}\nH for help",
+        model.generation,
+        model.population(),
+        model.clock.speed
+    );
+    draw.text(&hud)
+        .color(STEELBLUE)
+        .left_justify()
+        .x_y(window.left() + 100.0, window.top() - 50.0)
+        .w(180.0);
+    draw_sparkline(
+        &draw,
+        &model.stats,
+        window.left() + 10.0,
+        window.bottom() + 10.0,
+    );
+    if model.show_help {
+        draw_help(&draw);
+    }
+
     draw.to_frame(app, &frame).unwrap();
 }
 
-fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
-    if MouseButton::Right == button {
-        model.running = !model.running;
-        return;
-    }
-    if MouseButton::Left != button {
+fn draw_help(draw: &Draw) {
+    let keys: Vec<&str> = KEY_BINDINGS.iter().map(|(keys, _)| *keys).collect();
+    let actions: Vec<&str> = KEY_BINDINGS.iter().map(|(_, action)| *action).collect();
+    draw.rect()
+        .color(rgba(0.0, 0.0, 0.0, 0.8))
+        .x_y(0.0, 0.0)
+        .w_h(420.0, 440.0);
+    draw.text(&keys.join("\n"))
+        .color(PLUM)
+        .left_justify()
+        .x_y(-110.0, 0.0)
+        .w_h(180.0, 420.0);
+    draw.text(&actions.join("\n"))
+        .color(WHITE)
+        .left_justify()
+        .x_y(100.0, 0.0)
+        .w_h(220.0, 420.0);
+}
+
+// Population of the latest generations, in a small box with its bottom left at (x, y).
+fn draw_sparkline(draw: &Draw, stats: &Stats, x: f32, y: f32) {
+    let (width, height) = (200.0, 50.0);
+    let samples = stats.samples();
+    let latest: Vec<_> = samples
+        .iter()
+        .skip(samples.len().saturating_sub(width as usize))
+        .collect();
+    if latest.len() < 2 {
         return;
     }
+    let highest = latest
+        .iter()
+        .map(|sample| sample.population)
+        .max()
+        .unwrap()
+        .max(1);
+    let points = latest.iter().enumerate().map(|(i, sample)| {
+        let point_x = x + i as f32 * width / (latest.len() - 1) as f32;
+        let point_y = y + sample.population as f32 / highest as f32 * height;
+        pt2(point_x, point_y)
+    });
+    draw.rect()
+        .color(rgba(0.0, 0.0, 0.0, 0.6))
+        .x_y(x + width / 2.0, y + height / 2.0)
+        .w_h(width, height);
+    draw.polyline().weight(1.5).color(LIMEGREEN).points(points);
+}
 
+// The left button paints and the right one erases, along the drag or as the shape
+// with Shift held. Ctrl makes the left button erase too.
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
     let mouse_point = app.mouse.position();
-    model.toggle_mouse_grid(mouse_point);
+    let mods = &app.keys.mods;
+    model.navigation.mouse_pressed(button, mouse_point);
+    match button {
+        MouseButton::Left if model.stamp.is_some() => model.stamp_mouse_grid(mouse_point),
+        MouseButton::Left | MouseButton::Right => {
+            let status = match button == MouseButton::Right || mods.ctrl() {
+                true => GridStatus::NOT,
+                false => GridStatus::LIFE,
+            };
+            let shape = mods.shift().then_some(model.shape);
+            model.start_stroke(mouse_point, status, shape);
+        }
+        _ => {}
+    }
 }
 
-fn constraint_to_grid(point: f32) -> usize {
-    ((point + (MIDDLE as f32)) / (BLOCK_SIZE).to_f32().unwrap())
-        .floor()
-        .abs()
-        .to_usize()
-        .unwrap()
+fn mouse_released(_app: &App, model: &mut Model, button: MouseButton) {
+    model.navigation.mouse_released(button);
+    if let MouseButton::Left | MouseButton::Right = button {
+        model.end_stroke();
+    }
+}
+
+// Dragging with the middle button pans, with the others paints.
+fn mouse_moved(_app: &App, model: &mut Model, position: Point2) {
+    if let Some(delta) = model.navigation.mouse_moved(position) {
+        model.pan(delta);
+    }
+    model.continue_stroke(position);
+}
+
+fn mouse_wheel(app: &App, model: &mut Model, delta: MouseScrollDelta, _phase: TouchPhase) {
+    model.zoom(zoom_factor(delta), app.mouse.position());
+}
+
+// The bindings are listed in `KEY_BINDINGS` for the help overlay.
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    let args = ARGS.get().unwrap();
+    match key {
+        Key::Space => model.running = !model.running,
+        Key::Right if !model.running => model.step(),
+        Key::Up => model.clock.change_speed(true),
+        Key::Down => model.clock.change_speed(false),
+        Key::C => model.clear(),
+        Key::N => {
+            let seed = nannou::rand::random();
+            model.seed = Some(seed);
+            model.replace_with(&soup_from_args(args, seed));
+        }
+        Key::I => model.invert(),
+        Key::D => model.toggle_detection(),
+        Key::H => model.show_help = !model.show_help,
+        Key::F11 => {
+            let window = app.main_window();
+            window.set_fullscreen(!window.is_fullscreen());
+        }
+        Key::S => {
+            let path = args
+                .output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("game_of_life.rle"));
+            match pattern::save(&path, &model.live_cells(), &model.rule) {
+                Ok(_) => println!("Pattern saved to {}", path.display()),
+                Err(e) => println!("Cannot save pattern {e}"),
+            }
+        }
+        Key::W => {
+            let path = args
+                .session
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("game_of_life_session.json"));
+            match snapshot::save(&path, &model.session()) {
+                Ok(_) => println!("Session saved to {}", path.display()),
+                Err(e) => println!("Cannot save session {e}"),
+            }
+        }
+        Key::E => {
+            let path = &args.stats;
+            match model.stats.save(path) {
+                Ok(_) => println!("Statistics saved to {}", path.display()),
+                Err(e) => println!("Cannot save statistics {e}"),
+            }
+        }
+        Key::Z => model.undo(),
+        Key::Y => model.redo(),
+        // Rewinding while running would be stepped over on the next frame.
+        Key::Left if !model.running => model.step_back(),
+        Key::Key1 | Key::Key2 | Key::Key3 | Key::Key4 | Key::Key5 | Key::Key6 => {
+            let index = [
+                Key::Key1,
+                Key::Key2,
+                Key::Key3,
+                Key::Key4,
+                Key::Key5,
+                Key::Key6,
+            ]
+            .iter()
+            .position(|k| *k == key)
+            .unwrap();
+            model.stamp = Stamp::library().into_iter().nth(index);
+        }
+        Key::R => model.stamp = model.stamp.as_ref().map(Stamp::rotate),
+        Key::F => model.stamp = model.stamp.as_ref().map(Stamp::flip),
+        Key::Escape => model.stamp = None,
+        Key::T => model.shape = model.shape.next(),
+        _ => {}
+    }
 }
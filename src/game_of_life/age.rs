@@ -0,0 +1,266 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use nannou::prelude::*;
+
+use super::{Grid, GridStatus};
+
+// Generations it takes a live cell to go through the whole gradient.
+const AGE_SPAN: u32 = 50;
+// Brightness of a ghost just after its cell died.
+const GHOST_BRIGHTNESS: f32 = 0.5;
+
+// How long every cell has been alive, and the ghosts dead cells leave behind for a while.
+// Cells are kept by world coordinates, so the ages follow the pattern while panning.
+// Only the cells in the window are followed, and only the cells that changed are updated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ages {
+    generation: u64,
+    // Generation each live cell was born in.
+    born: HashMap<(i64, i64), u64>,
+    // Age at death and last generation alive.
+    ghosts: HashMap<(i64, i64), (u32, u64)>,
+    // Ghosts in the order they appeared, to let them go after `trail` generations.
+    deaths: VecDeque<(u64, (i64, i64))>,
+}
+
+impl Ages {
+    // Starts at `generation` with the live cells of the window `grid`, its corner at `origin`.
+    pub fn new(generation: u64, origin: (i64, i64), grid: &Grid) -> Self {
+        let mut ages = Ages {
+            generation,
+            ..Ages::default()
+        };
+        ages.show(origin, grid);
+        ages
+    }
+
+    // Generations alive, 1 for a cell just born.
+    pub fn age(&self, x: i64, y: i64) -> Option<u32> {
+        let born = self.born.get(&(x, y))?;
+        Some((self.generation - born + 1) as u32)
+    }
+
+    // Age at death and generations since then.
+    pub fn ghost(&self, x: i64, y: i64) -> Option<(u32, u32)> {
+        let (age, last_alive) = self.ghosts.get(&(x, y))?;
+        Some((*age, (self.generation - last_alive) as u32))
+    }
+
+    // A cell drawn is born now, one erased leaves no ghost.
+    pub fn set(&mut self, x: i64, y: i64, alive: bool) {
+        self.ghosts.remove(&(x, y));
+        match alive {
+            true => self.born.insert((x, y), self.generation),
+            false => self.born.remove(&(x, y)),
+        };
+    }
+
+    // Moves on to `generation` with the cells born, (x, y, true), and died, (x, y, false),
+    // since the last one. The cells that died leave a ghost for `trail` generations.
+    pub fn update(
+        &mut self,
+        generation: u64,
+        changes: impl IntoIterator<Item = (i64, i64, bool)>,
+        trail: u32,
+    ) {
+        let last_alive = self.generation;
+        self.generation = generation;
+        for (x, y, born) in changes {
+            if born {
+                self.born.insert((x, y), generation);
+                self.ghosts.remove(&(x, y));
+                continue;
+            }
+            let Some(born) = self.born.remove(&(x, y)) else {
+                continue;
+            };
+            if trail > 0 {
+                let age = (last_alive - born + 1) as u32;
+                self.ghosts.insert((x, y), (age, last_alive));
+                self.deaths.push_back((last_alive, (x, y)));
+            }
+        }
+        while let Some((died, position)) = self.deaths.front().copied() {
+            if generation - died <= trail as u64 {
+                break;
+            }
+            self.deaths.pop_front();
+            // The cell may have died again since, leaving a newer ghost.
+            if self.ghosts.get(&position).map(|ghost| ghost.1) == Some(died) {
+                self.ghosts.remove(&position);
+            }
+        }
+    }
+
+    // Follows the window `grid` after it moved to `origin`: the cells out of view are
+    // forgotten and the live cells coming into view start their age now.
+    pub fn show(&mut self, (x0, y0): (i64, i64), grid: &Grid) {
+        let (width, height) = (grid.len() as i64, grid.first().map_or(0, Vec::len) as i64);
+        let inside =
+            |(x, y): &(i64, i64)| (0..width).contains(&(x - x0)) && (0..height).contains(&(y - y0));
+        self.born.retain(|position, _| inside(position));
+        self.ghosts.retain(|position, _| inside(position));
+        for (x, col) in grid.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                if *cell == GridStatus::LIFE {
+                    let position = (x0 + x as i64, y0 + y as i64);
+                    self.born.entry(position).or_insert(self.generation);
+                }
+            }
+        }
+    }
+}
+
+// Colours live cells go through as they age, evenly spread over the first `AGE_SPAN` generations.
+// Written as comma separated `#rrggbb` colours, or one of the names `white`, `heat` and `ice`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    stops: Vec<Rgb>,
+}
+
+impl Gradient {
+    pub const WHITE: &'static str = "#ffffff";
+    pub const HEAT: &'static str = "#ffffff,#ffd700,#ff8c00,#dc143c,#4b0082";
+    pub const ICE: &'static str = "#ffffff,#87ceeb,#4682b4,#191970";
+
+    // A single colour, whatever the age.
+    pub fn is_flat(&self) -> bool {
+        self.stops.windows(2).all(|pair| pair[0] == pair[1])
+    }
+
+    // Colour of a cell alive for `age` generations.
+    pub fn at(&self, age: u32) -> Rgb {
+        let last = self.stops.len() - 1;
+        let t = age.saturating_sub(1).min(AGE_SPAN) as f32 / AGE_SPAN as f32 * last as f32;
+        let i = (t.floor() as usize).min(last.saturating_sub(1));
+        let (from, to) = (self.stops[i], self.stops[(i + 1).min(last)]);
+        let f = t - i as f32;
+        rgb(
+            from.red + (to.red - from.red) * f,
+            from.green + (to.green - from.green) * f,
+            from.blue + (to.blue - from.blue) * f,
+        )
+    }
+
+    // Colour of a ghost `since` generations after a cell died at `age`, out of `trail`.
+    pub fn ghost(&self, (age, since): (u32, u32), trail: u32) -> Rgb {
+        let color = self.at(age);
+        let brightness = GHOST_BRIGHTNESS * (trail + 1 - since) as f32 / (trail + 1) as f32;
+        rgb(
+            color.red * brightness,
+            color.green * brightness,
+            color.blue * brightness,
+        )
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient::from_str(Gradient::WHITE).unwrap()
+    }
+}
+
+impl FromStr for Gradient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = match s.trim().to_lowercase().as_str() {
+            "white" => Gradient::WHITE.to_string(),
+            "heat" => Gradient::HEAT.to_string(),
+            "ice" => Gradient::ICE.to_string(),
+            _ => s.trim().to_lowercase(),
+        };
+        let stops = colors
+            .split(',')
+            .map(|color| {
+                let hex = color.trim().trim_start_matches('#');
+                let channel = |i: usize| {
+                    hex.get(i..i + 2)
+                        .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                        .map(|channel| channel as f32 / 255.0)
+                };
+                match (hex.len(), channel(0), channel(2), channel(4)) {
+                    (6, Some(red), Some(green), Some(blue)) => Ok(rgb(red, green, blue)),
+                    _ => Err(format!(
+                        "invalid colour `{color}` in gradient `{s}`, expected #rrggbb"
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Gradient { stops })
+    }
+}
+
+impl fmt::Display for Gradient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |channel: f32| (channel * 255.0).round() as u8;
+        let colors: Vec<String> = self
+            .stops
+            .iter()
+            .map(|c| format!("#{:02x}{:02x}{:02x}", hex(c.red), hex(c.green), hex(c.blue)))
+            .collect();
+        write!(f, "{}", colors.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test_age {
+    use super::*;
+
+    fn grid(positions: &[(usize, usize)]) -> Grid {
+        let mut grid = vec![vec![GridStatus::NOT; 4]; 4];
+        for (x, y) in positions {
+            grid[*x][*y] = GridStatus::LIFE;
+        }
+        grid
+    }
+
+    #[test]
+    fn ages_and_ghosts() {
+        let mut ages = Ages::new(0, (0, 0), &grid(&[(0, 0), (1, 0)]));
+        assert_eq!(ages.age(0, 0), Some(1));
+        ages.update(1, [(1, 0, false), (2, 0, true)], 2);
+        assert_eq!(ages.age(0, 0), Some(2));
+        assert_eq!(ages.age(2, 0), Some(1));
+        assert_eq!(ages.ghost(1, 0), Some((1, 1)));
+
+        ages.update(2, [(2, 0, false)], 2);
+        assert_eq!(ages.age(0, 0), Some(3));
+        assert_eq!(ages.ghost(1, 0), Some((1, 2)));
+        assert_eq!(ages.ghost(2, 0), Some((1, 1)));
+
+        // Gone after `trail` generations, or as soon as the cell is born again.
+        ages.update(3, [(2, 0, true)], 2);
+        assert_eq!(ages.ghost(1, 0), None);
+        assert_eq!(ages.ghost(2, 0), None);
+        assert_eq!(ages.age(2, 0), Some(1));
+
+        // Drawn cells are born now, and moving the window forgets what left it.
+        ages.set(3, 3, true);
+        assert_eq!(ages.age(3, 3), Some(1));
+        ages.show((1, 0), &grid(&[(0, 0), (1, 3)]));
+        assert_eq!(ages.age(0, 0), None);
+        assert_eq!(ages.age(1, 0), Some(1));
+        assert_eq!(ages.age(2, 0), Some(1));
+    }
+
+    #[test]
+    fn gradient_colours() {
+        let gradient: Gradient = "#000000,#ff0000".parse().unwrap();
+        assert_eq!(gradient.at(1), rgb(0.0, 0.0, 0.0));
+        assert_eq!(gradient.at(1 + AGE_SPAN / 2), rgb(0.5, 0.0, 0.0));
+        assert_eq!(gradient.at(1000), rgb(1.0, 0.0, 0.0));
+        assert_eq!(Gradient::default().at(7), rgb(1.0, 1.0, 1.0));
+        assert_eq!("ICE".parse::<Gradient>().unwrap().at(1), rgb(1.0, 1.0, 1.0));
+
+        let ghost = "white".parse::<Gradient>().unwrap().ghost((4, 1), 3);
+        assert_eq!(ghost, rgb(0.375, 0.375, 0.375));
+
+        let heat: Gradient = "heat".parse().unwrap();
+        assert_eq!(heat.to_string(), Gradient::HEAT);
+        assert!("#fff".parse::<Gradient>().is_err());
+        assert!("white,#gg0000".parse::<Gradient>().is_err());
+    }
+}
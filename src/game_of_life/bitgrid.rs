@@ -0,0 +1,317 @@
+use rayon::prelude::*;
+
+use super::{Grid, GridStatus, Rule, Topology};
+
+// Rows a thread steps at once. Rows only read the current generation,
+// so bands are computed in parallel and give the same result as one after the other.
+const ROWS_PER_BAND: usize = 16;
+
+// 64 cells per `u64`, one row after the other.
+// A generation is computed a whole word at a time: the eight neighbour
+// bit planes are added with a bit-sliced adder and the rule picks the
+// counts that are born or survive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        BitGrid {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
+    }
+
+    pub fn from_grid(grid: &Grid) -> Self {
+        let mut bits = BitGrid::new(grid.len(), grid[0].len());
+        for (x, col) in grid.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                bits.set(x, y, *cell == GridStatus::LIFE);
+            }
+        }
+        bits
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let word = self.words[y * self.words_per_row + x / 64];
+        word >> (x % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        let word = &mut self.words[y * self.words_per_row + x / 64];
+        let bit = 1 << (x % 64);
+        if alive {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    pub fn population(&self) -> u64 {
+        self.words.iter().map(|word| word.count_ones() as u64).sum()
+    }
+
+    // Smallest rectangle holding every live cell, as (min_x, min_y, max_x, max_y).
+    pub fn bounding_box(&self) -> Option<(usize, usize, usize, usize)> {
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for y in 0..self.height {
+            for (i, word) in self.row(y).iter().enumerate() {
+                if *word == 0 {
+                    continue;
+                }
+                let x0 = i * 64 + word.trailing_zeros() as usize;
+                let x1 = i * 64 + 63 - word.leading_zeros() as usize;
+                bounds = Some(match bounds {
+                    None => (x0, y, x1, y),
+                    Some((a, b, c, _)) => (a.min(x0), b, c.max(x1), y),
+                });
+            }
+        }
+        bounds
+    }
+
+    pub fn live_cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        for y in 0..self.height {
+            for (i, word) in self.row(y).iter().enumerate() {
+                let mut word = *word;
+                while word != 0 {
+                    cells.push((i * 64 + word.trailing_zeros() as usize, y));
+                    word &= word - 1;
+                }
+            }
+        }
+        cells
+    }
+
+    fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    // Bits past `width` in the last word of a row, which must stay dead.
+    fn last_word_mask(&self) -> u64 {
+        match self.width % 64 {
+            0 => u64::MAX,
+            used => (1 << used) - 1,
+        }
+    }
+
+    // Only bounded and torus edges can be done with shifts.
+    pub fn supports(topology: Topology) -> bool {
+        matches!(topology, Topology::Bounded | Topology::Torus)
+    }
+
+    // Advances one generation, returning how many cells were born and how many died.
+    pub fn step(&mut self, rule: &Rule, topology: Topology) -> (u64, u64) {
+        let words = self.words_per_row;
+        let mut next = vec![0; self.words.len()];
+        // Neighbour counts that are born or survive.
+        let birth: Vec<usize> = (0..=8).filter(|n| rule.next(false, *n)).collect();
+        let survival: Vec<usize> = (0..=8).filter(|n| rule.next(true, *n)).collect();
+        let empty = vec![0; words];
+
+        next.par_chunks_mut(words * ROWS_PER_BAND)
+            .enumerate()
+            .for_each(|(band, rows)| {
+                // Neighbour planes of the current row: west, east, and the six above and below.
+                let mut planes = vec![vec![0; words]; 8];
+                for (i, out) in rows.chunks_mut(words).enumerate() {
+                    let y = band * ROWS_PER_BAND + i;
+                    self.step_row(y, topology, (&birth, &survival), &empty, &mut planes, out);
+                }
+            });
+        let count = |words: (&u64, &u64)| (words.0 & !words.1).count_ones() as u64;
+        let births = next.iter().zip(&self.words).map(count).sum();
+        let deaths = self.words.iter().zip(&next).map(count).sum();
+        self.words = next;
+        (births, deaths)
+    }
+
+    // Writes the next generation of row `y` to `out`.
+    fn step_row(
+        &self,
+        y: usize,
+        topology: Topology,
+        // Neighbour counts that are born and that survive.
+        (birth, survival): (&[usize], &[usize]),
+        empty: &[u64],
+        planes: &mut [Vec<u64>],
+        out: &mut [u64],
+    ) {
+        let wrap = topology == Topology::Torus;
+        let words = self.words_per_row;
+        let last_word_mask = self.last_word_mask();
+        let row_at = |dy: isize| -> &[u64] {
+            match topology.wrap(0, y as isize + dy, self.width, self.height) {
+                Some((_, y)) => self.row(y),
+                None => empty,
+            }
+        };
+        let (above, centre, below) = (row_at(-1), row_at(0), row_at(1));
+
+        let width = self.width;
+        let (west, rest) = planes.split_at_mut(1);
+        shift_east(centre, width, wrap, &mut west[0]);
+        shift_west(centre, width, wrap, &mut rest[0]);
+        shift_east(above, width, wrap, &mut rest[1]);
+        shift_west(above, width, wrap, &mut rest[2]);
+        shift_east(below, width, wrap, &mut rest[3]);
+        shift_west(below, width, wrap, &mut rest[4]);
+        rest[5].copy_from_slice(above);
+        rest[6].copy_from_slice(below);
+
+        for i in 0..words {
+            // Four bit planes hold the 0 to 8 neighbour count of 64 cells.
+            let mut count = [0u64; 4];
+            for plane in planes.iter() {
+                let mut carry = plane[i];
+                for bit in count.iter_mut() {
+                    let overflow = *bit & carry;
+                    *bit ^= carry;
+                    carry = overflow;
+                }
+            }
+            let equals = |n: usize| -> u64 {
+                count.iter().enumerate().fold(u64::MAX, |mask, (b, bit)| {
+                    mask & if n >> b & 1 == 1 { *bit } else { !*bit }
+                })
+            };
+            let born = birth.iter().fold(0, |mask, n| mask | equals(*n));
+            let survive = survival.iter().fold(0, |mask, n| mask | equals(*n));
+
+            let alive = centre[i];
+            let mut word = (alive & survive) | (!alive & born);
+            if i == words - 1 {
+                word &= last_word_mask;
+            }
+            out[i] = word;
+        }
+    }
+
+    // The whole board as a grid, for the tests to compare with the other engines.
+    #[cfg(test)]
+    pub fn to_grid(&self) -> Grid {
+        let mut grid = vec![vec![GridStatus::NOT; self.height]; self.width];
+        for (x, col) in grid.iter_mut().enumerate() {
+            for (y, cell) in col.iter_mut().enumerate() {
+                if self.get(x, y) {
+                    *cell = GridStatus::LIFE;
+                }
+            }
+        }
+        grid
+    }
+}
+
+// Row moved one cell east: bit `x` holds cell `x - 1`.
+fn shift_east(row: &[u64], width: usize, wrap: bool, out: &mut [u64]) {
+    let mut carry = 0;
+    for (i, word) in row.iter().enumerate() {
+        out[i] = (word << 1) | carry;
+        carry = word >> 63;
+    }
+    if wrap {
+        let last = width - 1;
+        out[0] |= row[last / 64] >> (last % 64) & 1;
+    }
+}
+
+// Row moved one cell west: bit `x` holds cell `x + 1`.
+fn shift_west(row: &[u64], width: usize, wrap: bool, out: &mut [u64]) {
+    let mut carry = 0;
+    for (i, word) in row.iter().enumerate().rev() {
+        out[i] = (word >> 1) | carry;
+        carry = word << 63;
+    }
+    if wrap {
+        let last = width - 1;
+        out[last / 64] |= (row[0] & 1) << (last % 64);
+    }
+}
+
+#[cfg(test)]
+mod test_bitgrid {
+    use nannou::rand::rngs::StdRng;
+    use nannou::rand::{Rng, SeedableRng};
+
+    use super::super::Model;
+    use super::*;
+
+    fn random_soup(rng: &mut StdRng, width: usize, height: usize, density: f64) -> Grid {
+        (0..width)
+            .map(|_| {
+                (0..height)
+                    .map(|_| match rng.gen_bool(density) {
+                        true => GridStatus::LIFE,
+                        false => GridStatus::NOT,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn get_set_and_population() {
+        let mut bits = BitGrid::new(130, 3);
+        bits.set(0, 0, true);
+        bits.set(64, 1, true);
+        bits.set(129, 2, true);
+        assert!(bits.get(64, 1));
+        assert!(!bits.get(65, 1));
+        assert_eq!(bits.population(), 3);
+        assert_eq!(bits.bounding_box(), Some((0, 0, 129, 2)));
+        assert_eq!(bits.live_cells(), vec![(0, 0), (64, 1), (129, 2)]);
+        bits.set(64, 1, false);
+        assert_eq!(bits.population(), 2);
+        assert_eq!(BitGrid::from_grid(&bits.to_grid()), bits);
+    }
+
+    #[test]
+    fn matches_game_rules_on_random_soups() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        for rule in [
+            Rule::CONWAY,
+            Rule::HIGH_LIFE,
+            Rule::SEEDS,
+            Rule::DAY_AND_NIGHT,
+        ] {
+            for topology in [Topology::Bounded, Topology::Torus] {
+                // Widths around the word size exercise the edge masks.
+                for (width, height) in [(70, 70), (64, 20), (130, 33), (5, 9)] {
+                    let mut model = Model {
+                        rule: rule.parse().unwrap(),
+                        topology,
+                        ..Model::new(None)
+                    };
+                    model.grid = random_soup(&mut rng, width, height, 0.4);
+                    let mut bits = BitGrid::from_grid(&model.grid);
+
+                    for generation in 0..20 {
+                        model.step();
+                        bits.step(&model.rule, topology);
+                        assert_eq!(
+                            bits.to_grid(),
+                            model.grid,
+                            "{rule} {topology:?} {width}x{height} generation {generation}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Cell sizes in pixels the zoom stays within.
+const MIN_CELL_SIZE: f32 = 2.0;
+const MAX_CELL_SIZE: f32 = 64.0;
+
+// Which part of the simulation the window shows.
+// Cell (x, y) covers [x, x + 1) x [y, y + 1) in world coordinates, and the
+// window centre, pixel (0, 0), looks at the world point `centre`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    pub centre: (f32, f32),
+    pub cell_size: f32,
+}
+
+impl Camera {
+    pub fn new(centre: (f32, f32), cell_size: f32) -> Self {
+        Camera { centre, cell_size }
+    }
+
+    // World point under a window point.
+    pub fn world_point(&self, point: Point2) -> (f32, f32) {
+        (
+            point.x / self.cell_size + self.centre.0,
+            point.y / self.cell_size + self.centre.1,
+        )
+    }
+
+    // Window point of a world point.
+    pub fn screen_point(&self, (x, y): (f32, f32)) -> Point2 {
+        pt2(
+            (x - self.centre.0) * self.cell_size,
+            (y - self.centre.1) * self.cell_size,
+        )
+    }
+
+    // Window point of the bottom left corner of a world cell.
+    pub fn screen_at(&self, x: i64, y: i64) -> Point2 {
+        self.screen_point((x as f32, y as f32))
+    }
+
+    // Cell of a `width` x `height` square grid, its corner at the world origin, under a
+    // window point.
+    pub fn cell_at(&self, point: Point2, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (x, y) = self.world_point(point);
+        let (x, y) = (x.floor(), y.floor());
+        let inside = x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height;
+        inside.then_some((x as usize, y as usize))
+    }
+
+    // World cells at least partly inside a `width` x `height` pixels window,
+    // as (min_x, min_y, max_x, max_y).
+    pub fn visible(&self, width: f32, height: f32) -> (i64, i64, i64, i64) {
+        let half_width = width / 2.0 / self.cell_size;
+        let half_height = height / 2.0 / self.cell_size;
+        (
+            (self.centre.0 - half_width).floor() as i64,
+            (self.centre.1 - half_height).floor() as i64,
+            (self.centre.0 + half_width).ceil() as i64 - 1,
+            (self.centre.1 + half_height).ceil() as i64 - 1,
+        )
+    }
+
+    // Moves the world along with a mouse drag of `delta` pixels.
+    pub fn pan(&mut self, delta: Vec2) {
+        self.centre.0 -= delta.x / self.cell_size;
+        self.centre.1 -= delta.y / self.cell_size;
+    }
+
+    // Scales the cells by `factor`, keeping the world point under `anchor` in place.
+    pub fn zoom(&mut self, factor: f32, anchor: Point2) {
+        let cell_size = (self.cell_size * factor).clamp(MIN_CELL_SIZE, MAX_CELL_SIZE);
+        let world_x = anchor.x / self.cell_size + self.centre.0;
+        let world_y = anchor.y / self.cell_size + self.centre.1;
+        self.centre = (
+            world_x - anchor.x / cell_size,
+            world_y - anchor.y / cell_size,
+        );
+        self.cell_size = cell_size;
+    }
+}
+
+#[cfg(test)]
+mod test_camera {
+    use super::*;
+
+    // Square cell under a window point.
+    fn cell_at(camera: &Camera, point: Point2) -> (i64, i64) {
+        let (x, y) = camera.world_point(point);
+        (x.floor() as i64, y.floor() as i64)
+    }
+
+    #[test]
+    fn picking_panning_and_zooming() {
+        let mut camera = Camera::new((30.0, 30.0), 10.0);
+        assert_eq!(cell_at(&camera, pt2(-300.0, -300.0)), (0, 0));
+        assert_eq!(cell_at(&camera, pt2(-0.1, 0.0)), (29, 30));
+        assert_eq!(camera.screen_at(0, 0), pt2(-300.0, -300.0));
+        assert_eq!(camera.visible(600.0, 600.0), (0, 0, 59, 59));
+        assert_eq!(camera.cell_at(pt2(-0.1, 0.0), 60, 60), Some((29, 30)));
+        assert_eq!(camera.cell_at(pt2(-0.1, 0.0), 29, 60), None);
+        assert_eq!(camera.cell_at(pt2(-301.0, 0.0), 60, 60), None);
+
+        // Half a cell of pan shows a sliver of one more column.
+        camera.pan(pt2(-5.0, 0.0));
+        assert_eq!(camera.visible(600.0, 600.0), (0, 0, 60, 59));
+
+        // The cell under the cursor stays under it.
+        let cursor = pt2(123.0, -47.0);
+        let under = cell_at(&camera, cursor);
+        camera.zoom(2.0, cursor);
+        assert_eq!(camera.cell_size, 20.0);
+        assert_eq!(cell_at(&camera, cursor), under);
+        camera.zoom(100.0, cursor);
+        assert_eq!(camera.cell_size, MAX_CELL_SIZE);
+        assert_eq!(cell_at(&camera, cursor), under);
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use nannou::prelude::*;
+
+// Steps per second while running, whatever the frame rate. Up and Down double and
+// halve the speed in every sketch with a clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clock {
+    pub speed: f64,
+    min_speed: f64,
+    max_speed: f64,
+    // A frame never runs more steps than this, a slow sketch drops the rest.
+    max_steps_per_frame: f64,
+    // Fraction of a step carried over to the next frame.
+    pending_steps: f64,
+}
+
+impl Clock {
+    pub fn new(speed: f64, min_speed: f64, max_speed: f64) -> Self {
+        Clock {
+            speed,
+            min_speed,
+            max_speed,
+            max_steps_per_frame: f64::INFINITY,
+            pending_steps: 0.0,
+        }
+    }
+
+    pub fn with_max_steps_per_frame(self, max_steps_per_frame: f64) -> Self {
+        Clock {
+            max_steps_per_frame,
+            ..self
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(self.min_speed, self.max_speed);
+    }
+
+    // Doubles or halves the steps per second.
+    pub fn change_speed(&mut self, faster: bool) {
+        let factor = if faster { 2.0 } else { 0.5 };
+        self.set_speed(self.speed * factor);
+    }
+
+    // Steps the speed asks for in `elapsed` time.
+    pub fn steps(&mut self, elapsed: Duration) -> usize {
+        self.pending_steps += elapsed.as_secs_f64() * self.speed;
+        let steps = self.pending_steps.floor().min(self.max_steps_per_frame);
+        self.pending_steps = match steps < self.max_steps_per_frame {
+            true => self.pending_steps - steps,
+            false => 0.0,
+        };
+        steps as usize
+    }
+}
+
+// Panning by dragging with the middle button held.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Navigation {
+    // Last mouse position while panning.
+    drag: Option<Point2>,
+}
+
+impl Navigation {
+    pub fn mouse_pressed(&mut self, button: MouseButton, point: Point2) {
+        if button == MouseButton::Middle {
+            self.drag = Some(point);
+        }
+    }
+
+    pub fn mouse_released(&mut self, button: MouseButton) {
+        if button == MouseButton::Middle {
+            self.drag = None;
+        }
+    }
+
+    // Pixels the world moves along with the mouse at `position`, while panning.
+    pub fn mouse_moved(&mut self, position: Point2) -> Option<Vec2> {
+        let last = self.drag?;
+        self.drag = Some(position);
+        Some(position - last)
+    }
+}
+
+// The wheel zooms around the cursor, a quarter bigger or smaller per notch.
+pub fn zoom_factor(delta: MouseScrollDelta) -> f32 {
+    let notches = match delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+    };
+    1.25f32.powf(notches)
+}
+
+#[cfg(test)]
+mod test_controls {
+    use super::*;
+
+    #[test]
+    fn clock_and_navigation() {
+        // Ten steps per second: two steps in a quarter second, the half step is kept.
+        let mut clock = Clock::new(10.0, 1.0, 20.0);
+        assert_eq!(clock.steps(Duration::from_millis(250)), 2);
+        assert_eq!(clock.steps(Duration::from_millis(50)), 1);
+        clock.change_speed(true);
+        clock.change_speed(true);
+        assert_eq!(clock.speed, 20.0);
+
+        // A frame past the limit drops the steps it could not run.
+        let mut clock = clock.with_max_steps_per_frame(4.0);
+        assert_eq!(clock.steps(Duration::from_secs(1)), 4);
+        assert_eq!(clock.steps(Duration::from_millis(60)), 1);
+
+        let mut navigation = Navigation::default();
+        assert_eq!(navigation.mouse_moved(pt2(5.0, 5.0)), None);
+        navigation.mouse_pressed(MouseButton::Middle, pt2(5.0, 5.0));
+        assert_eq!(navigation.mouse_moved(pt2(8.0, 1.0)), Some(vec2(3.0, -4.0)));
+        navigation.mouse_released(MouseButton::Middle);
+        assert_eq!(navigation.mouse_moved(pt2(9.0, 1.0)), None);
+        assert_eq!(zoom_factor(MouseScrollDelta::LineDelta(0.0, 2.0)), 1.5625);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use super::GridStatus;
+
+// Generations remembered before the history starts over.
+const MAX_HISTORY: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Classification {
+    Extinct,
+    StillLife,
+    Oscillator { period: u64 },
+    // Moves (dx, dy) cells every `period` generations.
+    Spaceship { period: u64, dx: i64, dy: i64 },
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Classification::Extinct => write!(f, "extinct"),
+            Classification::StillLife => write!(f, "still life"),
+            Classification::Oscillator { period } => write!(f, "oscillator p{period}"),
+            Classification::Spaceship { period, dx, dy } => {
+                write!(f, "spaceship p{period} dx={dx} dy={dy}")
+            }
+        }
+    }
+}
+
+// Hashes every generation's shape, ignoring where it is, and looks for a repeat.
+// The generations between repeats give the period and the moved corner the displacement.
+#[derive(Clone, Debug, Default)]
+pub struct Detector {
+    // Shape hash to the generation and bottom left corner it was seen at.
+    seen: HashMap<u64, (u64, i64, i64)>,
+    // Shape hash and corner of the last generation seen.
+    last: Option<(u64, i64, i64)>,
+    result: Option<Classification>,
+}
+
+impl Detector {
+    pub fn result(&self) -> Option<Classification> {
+        self.result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    // `cells` are the live and dying cells of the generation, in any order.
+    pub fn observe(&mut self, generation: u64, cells: &[(i64, i64, GridStatus)]) {
+        let Some((hash, min_x, min_y)) = shape(cells) else {
+            self.result = Some(Classification::Extinct);
+            return;
+        };
+
+        // A shape that was never seen means the pattern is still changing.
+        self.result = self.seen.get(&hash).map(|(seen_at, seen_x, seen_y)| {
+            classify(generation - seen_at, min_x - seen_x, min_y - seen_y)
+        });
+
+        if self.seen.len() >= MAX_HISTORY {
+            self.seen.clear();
+        }
+        self.seen.insert(hash, (generation, min_x, min_y));
+        self.last = Some((hash, min_x, min_y));
+    }
+
+    // With steps of `step` generations, a power of two, the shape found again may have
+    // come back sooner, after any number of generations dividing the period. Tries them
+    // from the shortest with `cells_after`, the cells that many generations after the
+    // last generation seen.
+    pub fn refine(
+        &mut self,
+        step: u64,
+        mut cells_after: impl FnMut(u64) -> Vec<(i64, i64, GridStatus)>,
+    ) {
+        let period = match self.result {
+            Some(
+                Classification::Oscillator { period } | Classification::Spaceship { period, .. },
+            ) => period,
+            _ => return,
+        };
+        let Some((hash, x, y)) = self.last else {
+            return;
+        };
+        let steps = period / step;
+        let mut divisors: Vec<u64> = (1..=steps)
+            .filter(|e| steps.is_multiple_of(*e))
+            .flat_map(|e| (0..=step.trailing_zeros()).map(move |a| e << a))
+            .filter(|d| *d < period)
+            .collect();
+        divisors.sort();
+        divisors.dedup();
+        for d in divisors {
+            if let Some((after, after_x, after_y)) = shape(&cells_after(d)) {
+                if after == hash {
+                    self.result = Some(classify(d, after_x - x, after_y - y));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Hash of the cells relative to their bottom left corner, and the corner.
+fn shape(cells: &[(i64, i64, GridStatus)]) -> Option<(u64, i64, i64)> {
+    let min_x = cells.iter().map(|cell| cell.0).min()?;
+    let min_y = cells.iter().map(|cell| cell.1).min()?;
+
+    let mut shape: Vec<(i64, i64, u8)> = cells
+        .iter()
+        .map(|(x, y, status)| {
+            let state = match status {
+                GridStatus::NOT => 0,
+                GridStatus::LIFE => 1,
+                GridStatus::DYING(age) => age + 1,
+            };
+            (x - min_x, y - min_y, state)
+        })
+        .collect();
+    shape.sort();
+    let mut hasher = DefaultHasher::new();
+    shape.hash(&mut hasher);
+    Some((hasher.finish(), min_x, min_y))
+}
+
+// A shape coming back after `period` generations, moved by (dx, dy).
+fn classify(period: u64, dx: i64, dy: i64) -> Classification {
+    match (dx, dy) {
+        (0, 0) if period <= 1 => Classification::StillLife,
+        (0, 0) => Classification::Oscillator { period },
+        (dx, dy) => Classification::Spaceship { period, dx, dy },
+    }
+}
+
+#[cfg(test)]
+mod test_detector {
+    use super::super::pattern::{place_centered, read_rle};
+    use super::super::Model;
+    use super::*;
+
+    fn classify(rle: &str, generations: usize) -> Option<Classification> {
+        let mut model = Model::new(None);
+        model.detect = true;
+        place_centered(&mut model.grid, &read_rle(rle).unwrap());
+        for _ in 0..generations {
+            model.step();
+        }
+        model.detector.result()
+    }
+
+    #[test]
+    fn classify_patterns() {
+        assert_eq!(
+            classify("x = 2, y = 2\n2o$2o!", 3),
+            Some(Classification::StillLife)
+        );
+        assert_eq!(
+            classify("x = 3, y = 1\n3o!", 3),
+            Some(Classification::Oscillator { period: 2 })
+        );
+        // Rows are listed from the top, so the glider heads down and right.
+        assert_eq!(
+            classify("x = 3, y = 3\nbo$2bo$3o!", 6),
+            Some(Classification::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: -1
+            })
+        );
+        assert_eq!(
+            classify("x = 1, y = 1\no!", 1),
+            Some(Classification::Extinct)
+        );
+        // The R-pentomino takes over a thousand generations to settle.
+        assert_eq!(classify("x = 3, y = 3\nb2o$2o$bo!", 20), None);
+    }
+
+    #[test]
+    fn detection_is_off_by_default() {
+        let mut model = Model::new(None);
+        place_centered(&mut model.grid, &read_rle("x = 3, y = 1\n3o!").unwrap());
+        model.step();
+        model.step();
+        assert!(model.detector.is_empty());
+        assert_eq!(model.detector.result(), None);
+    }
+
+    #[test]
+    fn periods_in_generations_with_hashlife() {
+        // Each step is 8 generations, which every one of these periods divides.
+        let classify = |rle: &str| {
+            let mut model = Model::new(None);
+            model.detect = true;
+            let pattern = read_rle(rle).unwrap();
+            model.use_hashlife(&pattern.cells, 3).unwrap();
+            for _ in 0..3 {
+                model.step();
+            }
+            model.detector.result()
+        };
+        assert_eq!(
+            classify("x = 2, y = 2\n2o$2o!"),
+            Some(Classification::StillLife)
+        );
+        assert_eq!(
+            classify("x = 3, y = 1\n3o!"),
+            Some(Classification::Oscillator { period: 2 })
+        );
+        assert_eq!(
+            classify("x = 3, y = 3\nbo$2bo$3o!"),
+            Some(Classification::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: -1
+            })
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// Shapes drawn by dragging with Shift held, from the cell where the drag started to the
+// cell under the mouse. `T` switches between them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Line,
+    Rectangle,
+    FilledRectangle,
+}
+
+impl Shape {
+    pub fn next(&self) -> Shape {
+        match self {
+            Shape::Line => Shape::Rectangle,
+            Shape::Rectangle => Shape::FilledRectangle,
+            Shape::FilledRectangle => Shape::Line,
+        }
+    }
+
+    // Cells of the shape between the corners or ends `from` and `to`.
+    pub fn cells(&self, from: (i64, i64), to: (i64, i64)) -> Vec<(i64, i64)> {
+        match self {
+            Shape::Line => line(from, to),
+            Shape::Rectangle => rectangle(from, to, false),
+            Shape::FilledRectangle => rectangle(from, to, true),
+        }
+    }
+}
+
+// Cells on the straight line from `from` to `to`, both included, with Bresenham's
+// algorithm: one cell per step along the longer axis, no gaps and no doubled corners.
+// https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+pub fn line((x0, y0): (i64, i64), (x1, y1): (i64, i64)) -> Vec<(i64, i64)> {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let mut error = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+    let mut cells = vec![(x, y)];
+    while (x, y) != (x1, y1) {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x, y));
+    }
+    cells
+}
+
+// Cells of the rectangle with opposite corners `from` and `to`, only its border unless `filled`.
+fn rectangle((x0, y0): (i64, i64), (x1, y1): (i64, i64), filled: bool) -> Vec<(i64, i64)> {
+    let (left, right) = (x0.min(x1), x0.max(x1));
+    let (bottom, top) = (y0.min(y1), y0.max(y1));
+    let mut cells = vec![];
+    for x in left..=right {
+        for y in bottom..=top {
+            if filled || x == left || x == right || y == bottom || y == top {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod test_drawing {
+    use super::*;
+
+    #[test]
+    fn bresenham_lines() {
+        assert_eq!(line((0, 0), (0, 0)), [(0, 0)]);
+        assert_eq!(line((0, 0), (3, 0)), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(line((2, 2), (0, 0)), [(2, 2), (1, 1), (0, 0)]);
+        assert_eq!(
+            line((0, 0), (4, 2)),
+            [(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]
+        );
+        assert_eq!(
+            line((0, 0), (-1, -3)),
+            [(0, 0), (0, -1), (-1, -2), (-1, -3)]
+        );
+
+        // Every step moves to a touching cell, whatever the direction.
+        for end in [(7, 3), (-5, 9), (2, -11), (-6, -6)] {
+            let cells = line((1, 1), end);
+            assert_eq!(
+                cells.len() as i64,
+                (end.0 - 1).abs().max((end.1 - 1).abs()) + 1
+            );
+            assert!(cells.windows(2).all(
+                |pair| (pair[0].0 - pair[1].0).abs() <= 1 && (pair[0].1 - pair[1].1).abs() <= 1
+            ));
+        }
+    }
+
+    #[test]
+    fn rectangles() {
+        let hollow = Shape::Rectangle.cells((3, 2), (0, 0));
+        assert_eq!(hollow.len(), 10);
+        assert!(!hollow.contains(&(1, 1)));
+        let filled = Shape::FilledRectangle.cells((3, 2), (0, 0));
+        assert_eq!(filled.len(), 12);
+        assert!(filled.contains(&(1, 1)));
+        assert_eq!(Shape::Rectangle.cells((5, 5), (5, 5)), [(5, 5)]);
+        assert_eq!(Shape::FilledRectangle.next(), Shape::Line);
+    }
+}
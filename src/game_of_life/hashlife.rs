@@ -0,0 +1,634 @@
+use std::collections::HashMap;
+
+use super::tiling::Tiling;
+use super::Rule;
+
+// Hashlife: the universe is a quadtree where equal subtrees are shared (hash-consed)
+// and the future of every subtree is memoized, so repeating patterns advance
+// by 2^k generations at a time.
+// https://conwaylife.com/wiki/HashLife
+//
+// Nodes live in an arena and are referred to by index.
+// Level 0 nodes are single cells, a level `n` node is a 2^n x 2^n square.
+// Quadrants are named with north as the smaller `y`.
+type NodeId = u32;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+// Past this many nodes the arena should be rebuilt with only what is still used.
+const MAX_NODES: usize = 1 << 22;
+// Largest k a step can advance 2^k generations by. The root grows to level k + 3, and
+// one more when the pattern needs padding, and a level 63 root is past i64 coordinates.
+pub const MAX_STEP_EXPONENT: u8 = 58;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    nw: NodeId,
+    ne: NodeId,
+    sw: NodeId,
+    se: NodeId,
+    level: u8,
+    population: u64,
+}
+
+// A past state of the universe, its root node, to go back to with `set_root`.
+// Garbage collection keeps and renumbers the roots it is given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Root(NodeId);
+
+#[derive(Clone, Debug)]
+pub struct Universe {
+    rule: Rule,
+    nodes: Vec<Node>,
+    // Hash-consing: children to the node built from them.
+    joined: HashMap<[NodeId; 4], NodeId>,
+    // (node, k) to the centre of the node 2^k generations later.
+    advanced: HashMap<(NodeId, u8), NodeId>,
+    // One empty node per level.
+    empty: Vec<NodeId>,
+    // The root is centred on the origin: it covers [-2^(level-1), 2^(level-1)) on both axes.
+    root: NodeId,
+    generation: u64,
+}
+
+impl Universe {
+    pub fn new(rule: Rule) -> Result<Self, String> {
+        // Empty space has to stay empty, or the quadtree stops being sparse.
+        if rule.next(false, 0) {
+            return Err(format!("hashlife cannot run B0 rules like {rule}"));
+        }
+        if rule.states() > 2 {
+            return Err(format!("hashlife cannot run Generations rules like {rule}"));
+        }
+        if rule.tiling() != Tiling::Square || rule.larger().is_some() {
+            return Err(format!(
+                "hashlife only runs square cells and their eight neighbours, not {rule}"
+            ));
+        }
+        let leaf = |population| Node {
+            nw: DEAD,
+            ne: DEAD,
+            sw: DEAD,
+            se: DEAD,
+            level: 0,
+            population,
+        };
+        let mut universe = Universe {
+            rule,
+            nodes: vec![leaf(0), leaf(1)],
+            joined: HashMap::new(),
+            advanced: HashMap::new(),
+            empty: vec![DEAD],
+            root: DEAD,
+            generation: 0,
+        };
+        universe.root = universe.empty(3);
+        Ok(universe)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    pub fn population(&self) -> u64 {
+        self.node(self.root).population
+    }
+
+    pub fn root(&self) -> Root {
+        Root(self.root)
+    }
+
+    // Goes back to a root of this universe, in its current arena.
+    pub fn set_root(&mut self, root: Root) {
+        self.root = root.0;
+    }
+
+    // Empties the universe, keeping the nodes past roots still use.
+    pub fn clear(&mut self) {
+        self.root = self.empty(3);
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id as usize]
+    }
+
+    fn level(&self, id: NodeId) -> u8 {
+        self.node(id).level
+    }
+
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        if let Some(id) = self.joined.get(&[nw, ne, sw, se]) {
+            return *id;
+        }
+        let population = [nw, ne, sw, se]
+            .iter()
+            .map(|child| self.node(*child).population)
+            .sum();
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            nw,
+            ne,
+            sw,
+            se,
+            level: self.level(nw) + 1,
+            population,
+        });
+        self.joined.insert([nw, ne, sw, se], id);
+        id
+    }
+
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let child = *self.empty.last().unwrap();
+            let id = self.join(child, child, child, child);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    fn half(&self) -> i64 {
+        1 << (self.level(self.root) - 1)
+    }
+
+    // Doubles the root size keeping the current root in the middle.
+    fn expand(&mut self) {
+        let Node {
+            nw,
+            ne,
+            sw,
+            se,
+            level,
+            ..
+        } = *self.node(self.root);
+        let e = self.empty(level - 1);
+        let nw = self.join(e, e, e, nw);
+        let ne = self.join(e, e, ne, e);
+        let sw = self.join(e, sw, e, e);
+        let se = self.join(se, e, e, e);
+        self.root = self.join(nw, ne, sw, se);
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        let half = self.half();
+        -half <= x && x < half && -half <= y && y < half
+    }
+
+    pub fn get_cell(&self, x: i64, y: i64) -> bool {
+        if !self.contains(x, y) {
+            return false;
+        }
+        let half = self.half();
+        let (mut x, mut y) = (x + half, y + half);
+        let mut id = self.root;
+        while self.level(id) > 0 {
+            let node = self.node(id);
+            let half = 1 << (node.level - 1);
+            id = match (x >= half, y >= half) {
+                (false, false) => node.nw,
+                (true, false) => node.ne,
+                (false, true) => node.sw,
+                (true, true) => node.se,
+            };
+            x %= half;
+            y %= half;
+        }
+        id == ALIVE
+    }
+
+    pub fn set_cell(&mut self, x: i64, y: i64, alive: bool) {
+        while !self.contains(x, y) {
+            self.expand();
+        }
+        let half = self.half();
+        self.root = self.set(self.root, x + half, y + half, alive);
+    }
+
+    fn set(&mut self, id: NodeId, x: i64, y: i64, alive: bool) -> NodeId {
+        let node = *self.node(id);
+        if node.level == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
+        let half = 1 << (node.level - 1);
+        let (x_in, y_in) = (x % half, y % half);
+        let Node { nw, ne, sw, se, .. } = node;
+        match (x >= half, y >= half) {
+            (false, false) => {
+                let nw = self.set(nw, x_in, y_in, alive);
+                self.join(nw, ne, sw, se)
+            }
+            (true, false) => {
+                let ne = self.set(ne, x_in, y_in, alive);
+                self.join(nw, ne, sw, se)
+            }
+            (false, true) => {
+                let sw = self.set(sw, x_in, y_in, alive);
+                self.join(nw, ne, sw, se)
+            }
+            (true, true) => {
+                let se = self.set(se, x_in, y_in, alive);
+                self.join(nw, ne, sw, se)
+            }
+        }
+    }
+
+    // Live cells inside [x0, x1) x [y0, y1).
+    pub fn live_cells(&self, x0: i64, y0: i64, x1: i64, y1: i64) -> Vec<(i64, i64)> {
+        let mut cells = vec![];
+        let half = self.half();
+        self.collect(self.root, -half, -half, (x0, y0, x1, y1), &mut cells);
+        cells
+    }
+
+    fn collect(
+        &self,
+        id: NodeId,
+        x: i64,
+        y: i64,
+        area: (i64, i64, i64, i64),
+        cells: &mut Vec<(i64, i64)>,
+    ) {
+        let node = self.node(id);
+        let size = 1 << node.level;
+        let (x0, y0, x1, y1) = area;
+        if node.population == 0 || x >= x1 || y >= y1 || x + size <= x0 || y + size <= y0 {
+            return;
+        }
+        if node.level == 0 {
+            cells.push((x, y));
+            return;
+        }
+        let half = size / 2;
+        self.collect(node.nw, x, y, area, cells);
+        self.collect(node.ne, x + half, y, area, cells);
+        self.collect(node.sw, x, y + half, area, cells);
+        self.collect(node.se, x + half, y + half, area, cells);
+    }
+
+    // Smallest rectangle holding every live cell, as (min_x, min_y, max_x, max_y).
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        let half = self.half();
+        let mut bounds = HashMap::new();
+        self.bounds(self.root, &mut bounds)
+            .map(|(x0, y0, x1, y1)| (x0 - half, y0 - half, x1 - half, y1 - half))
+    }
+
+    // Bounds of a node relative to its corner, memoized as nodes are shared.
+    fn bounds(
+        &self,
+        id: NodeId,
+        memo: &mut HashMap<NodeId, Option<(i64, i64, i64, i64)>>,
+    ) -> Option<(i64, i64, i64, i64)> {
+        let node = *self.node(id);
+        if node.population == 0 {
+            return None;
+        }
+        if node.level == 0 {
+            return Some((0, 0, 0, 0));
+        }
+        if let Some(bounds) = memo.get(&id) {
+            return *bounds;
+        }
+        let half = 1 << (node.level - 1);
+        let quadrants = [
+            (node.nw, 0, 0),
+            (node.ne, half, 0),
+            (node.sw, 0, half),
+            (node.se, half, half),
+        ];
+        let mut result: Option<(i64, i64, i64, i64)> = None;
+        for (child, dx, dy) in quadrants {
+            if let Some((x0, y0, x1, y1)) = self.bounds(child, memo) {
+                let (x0, y0, x1, y1) = (x0 + dx, y0 + dy, x1 + dx, y1 + dy);
+                result = Some(match result {
+                    None => (x0, y0, x1, y1),
+                    Some((a, b, c, d)) => (a.min(x0), b.min(y0), c.max(x1), d.max(y1)),
+                });
+            }
+        }
+        memo.insert(id, result);
+        result
+    }
+
+    // Centre of a node, one level down.
+    fn centre(&mut self, id: NodeId) -> NodeId {
+        let Node { nw, ne, sw, se, .. } = *self.node(id);
+        let (nw, ne, sw, se) = (
+            self.node(nw).se,
+            self.node(ne).sw,
+            self.node(sw).ne,
+            self.node(se).nw,
+        );
+        self.join(nw, ne, sw, se)
+    }
+
+    // Node straddling the border between two side by side nodes.
+    fn centre_horizontal(&mut self, w: NodeId, e: NodeId) -> NodeId {
+        let (w, e) = (*self.node(w), *self.node(e));
+        self.join(w.ne, e.nw, w.se, e.sw)
+    }
+
+    // Node straddling the border between two stacked nodes.
+    fn centre_vertical(&mut self, n: NodeId, s: NodeId) -> NodeId {
+        let (n, s) = (*self.node(n), *self.node(s));
+        self.join(n.sw, n.se, s.nw, s.ne)
+    }
+
+    // One generation of the centre 2x2 of a 4x4 node, brute force.
+    fn step_level_2(&mut self, id: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (y, row) in cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = self.cell_in(id, x as i64, y as i64);
+            }
+        }
+        let mut next = [DEAD; 4];
+        for (i, (x, y)) in [(1, 1), (2, 1), (1, 2), (2, 2)].into_iter().enumerate() {
+            let mut neighbors = 0;
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    if (dx, dy) != (1, 1) && cells[y + dy - 1][x + dx - 1] {
+                        neighbors += 1;
+                    }
+                }
+            }
+            if self.rule.next(cells[y][x], neighbors) {
+                next[i] = ALIVE;
+            }
+        }
+        self.join(next[0], next[1], next[2], next[3])
+    }
+
+    fn cell_in(&self, id: NodeId, x: i64, y: i64) -> bool {
+        let node = self.node(id);
+        if node.level == 0 {
+            return id == ALIVE;
+        }
+        let half = 1 << (node.level - 1);
+        let child = match (x >= half, y >= half) {
+            (false, false) => node.nw,
+            (true, false) => node.ne,
+            (false, true) => node.sw,
+            (true, true) => node.se,
+        };
+        self.cell_in(child, x % half, y % half)
+    }
+
+    // Centre of a level `n` node, 2^k generations later, with k <= n - 2.
+    fn advance(&mut self, id: NodeId, k: u8) -> NodeId {
+        let node = *self.node(id);
+        if node.population == 0 {
+            return self.empty(node.level - 1);
+        }
+        if let Some(result) = self.advanced.get(&(id, k)) {
+            return *result;
+        }
+        let result = if node.level == 2 {
+            self.step_level_2(id)
+        } else {
+            // Nine overlapping sub-squares, one level down.
+            let n00 = node.nw;
+            let n01 = self.centre_horizontal(node.nw, node.ne);
+            let n02 = node.ne;
+            let n10 = self.centre_vertical(node.nw, node.sw);
+            let n11 = self.centre(id);
+            let n12 = self.centre_vertical(node.ne, node.se);
+            let n20 = node.sw;
+            let n21 = self.centre_horizontal(node.sw, node.se);
+            let n22 = node.se;
+
+            // At full speed both halves advance 2^(k-1), otherwise only the second one moves.
+            let full_speed = k == node.level - 2;
+            let first = |universe: &mut Universe, sub: NodeId| {
+                if full_speed {
+                    universe.advance(sub, k - 1)
+                } else {
+                    universe.centre(sub)
+                }
+            };
+            let m00 = first(self, n00);
+            let m01 = first(self, n01);
+            let m02 = first(self, n02);
+            let m10 = first(self, n10);
+            let m11 = first(self, n11);
+            let m12 = first(self, n12);
+            let m20 = first(self, n20);
+            let m21 = first(self, n21);
+            let m22 = first(self, n22);
+
+            let second_k = if full_speed { k - 1 } else { k };
+            let nw = self.join(m00, m01, m10, m11);
+            let ne = self.join(m01, m02, m11, m12);
+            let sw = self.join(m10, m11, m20, m21);
+            let se = self.join(m11, m12, m21, m22);
+            let nw = self.advance(nw, second_k);
+            let ne = self.advance(ne, second_k);
+            let sw = self.advance(sw, second_k);
+            let se = self.advance(se, second_k);
+            self.join(nw, ne, sw, se)
+        };
+        self.advanced.insert((id, k), result);
+        result
+    }
+
+    // Advances the whole universe by 2^k generations.
+    pub fn step(&mut self, k: u8) {
+        // Live cells have to sit in the centre quarter with room to travel 2^k cells
+        // at the speed of light before reaching the edge of the returned centre.
+        loop {
+            let level = self.level(self.root);
+            let inner = self.centre(self.root);
+            let inner = self.centre(inner);
+            let padded = self.node(inner).population == self.population();
+            if padded && level >= k + 3 {
+                break;
+            }
+            self.expand();
+        }
+        self.root = self.advance(self.root, k);
+        self.generation += 1 << k;
+    }
+
+    // Live cells `generations` from now, leaving the universe where it is.
+    // The nodes built on the way stay memoized until garbage is collected.
+    pub fn cells_after(&mut self, generations: u64) -> Vec<(i64, i64)> {
+        let (root, generation) = (self.root, self.generation);
+        for k in 0..=MAX_STEP_EXPONENT {
+            if generations >> k & 1 == 1 {
+                self.step(k);
+            }
+        }
+        let cells = match self.bounding_box() {
+            Some((x0, y0, x1, y1)) => self.live_cells(x0, y0, x1 + 1, y1 + 1),
+            None => vec![],
+        };
+        (self.root, self.generation) = (root, generation);
+        cells
+    }
+
+    // Whether the arena grew past the point where garbage should be collected.
+    pub fn is_crowded(&self) -> bool {
+        self.nodes.len() > MAX_NODES
+    }
+
+    // Rebuilds the arena from the root and the `kept` past roots, dropping unreachable
+    // nodes and the memo. The kept roots are renumbered to their place in the new arena.
+    pub fn collect_garbage<'a>(&mut self, kept: impl IntoIterator<Item = &'a mut Root>) {
+        let mut fresh = Universe::new(self.rule).unwrap();
+        let mut copied = HashMap::new();
+        fresh.root = fresh.copy_from(self, self.root, &mut copied);
+        for root in kept {
+            root.0 = fresh.copy_from(self, root.0, &mut copied);
+        }
+        fresh.generation = self.generation;
+        *self = fresh;
+    }
+
+    fn copy_from(
+        &mut self,
+        other: &Universe,
+        id: NodeId,
+        copied: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        if id == DEAD || id == ALIVE {
+            return id;
+        }
+        if let Some(new_id) = copied.get(&id) {
+            return *new_id;
+        }
+        let node = *other.node(id);
+        let nw = self.copy_from(other, node.nw, copied);
+        let ne = self.copy_from(other, node.ne, copied);
+        let sw = self.copy_from(other, node.sw, copied);
+        let se = self.copy_from(other, node.se, copied);
+        let new_id = self.join(nw, ne, sw, se);
+        copied.insert(id, new_id);
+        new_id
+    }
+}
+
+#[cfg(test)]
+mod test_hashlife {
+    use super::*;
+
+    fn universe_with(cells: &[(i64, i64)]) -> Universe {
+        let mut universe = Universe::new(Rule::default()).unwrap();
+        for (x, y) in cells {
+            universe.set_cell(*x, *y, true);
+        }
+        universe
+    }
+
+    #[test]
+    fn set_and_get_cells() {
+        let mut universe = universe_with(&[(0, 0), (-5, 3), (100, -200)]);
+        assert!(universe.get_cell(100, -200));
+        assert!(universe.get_cell(-5, 3));
+        assert!(!universe.get_cell(1, 0));
+        assert_eq!(universe.population(), 3);
+
+        assert_eq!(universe.bounding_box(), Some((-5, -200, 100, 3)));
+
+        universe.set_cell(0, 0, false);
+        assert_eq!(universe.population(), 2);
+        assert_eq!(universe.live_cells(-10, -10, 10, 10), vec![(-5, 3)]);
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let mut universe = universe_with(&[(-1, 0), (0, 0), (1, 0)]);
+        universe.step(0);
+        assert_eq!(universe.generation(), 1);
+        let mut cells = universe.live_cells(-5, -5, 5, 5);
+        cells.sort();
+        assert_eq!(cells, vec![(0, -1), (0, 0), (0, 1)]);
+        // Any even number of generations lands back on the start.
+        universe.step(5);
+        assert_eq!(universe.generation(), 33);
+        assert_eq!(universe.population(), 3);
+        assert!(universe.get_cell(0, 1));
+    }
+
+    #[test]
+    fn glider_travels_far() {
+        // Moves one cell diagonally every 4 generations.
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut universe = universe_with(&glider);
+        universe.step(10);
+        assert_eq!(universe.population(), 5);
+        for (x, y) in glider {
+            assert!(universe.get_cell(x + 256, y + 256));
+        }
+
+        // Past roots survive garbage collection.
+        let mut past = universe.root();
+        universe.step(2);
+        universe.collect_garbage([&mut past]);
+        universe.set_root(past);
+        universe.set_generation(1024);
+        assert!(universe.get_cell(257, 256));
+
+        // Looking ahead leaves the glider where it is.
+        let mut ahead = universe.cells_after(12);
+        ahead.sort();
+        let mut expected: Vec<_> = glider.iter().map(|(x, y)| (x + 259, y + 259)).collect();
+        expected.sort();
+        assert_eq!(ahead, expected);
+        assert_eq!(universe.generation(), 1024);
+        assert!(universe.get_cell(257, 256));
+
+        // The largest step still leaves coordinates the live cells can be read at, even
+        // with a block far enough out for the root to need padding.
+        let far = 1 << (MAX_STEP_EXPONENT + 1);
+        let mut universe = universe_with(&[(far, 0), (far + 1, 0), (far, 1), (far + 1, 1)]);
+        universe.step(MAX_STEP_EXPONENT);
+        let mut cells = universe.live_cells(i64::MIN, i64::MIN, i64::MAX, i64::MAX);
+        cells.sort();
+        assert_eq!(cells, [(far, 0), (far, 1), (far + 1, 0), (far + 1, 1)]);
+    }
+
+    #[test]
+    fn r_pentomino_matches_brute_force() {
+        use std::collections::HashSet;
+
+        let r_pentomino = [(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)];
+        let mut cells: HashSet<(i64, i64)> = r_pentomino.into_iter().collect();
+        let rule = Rule::default();
+        for _ in 0..128 {
+            let candidates: HashSet<(i64, i64)> = cells
+                .iter()
+                .flat_map(|(x, y)| {
+                    (-1..=1).flat_map(move |dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+                })
+                .collect();
+            cells = candidates
+                .into_iter()
+                .filter(|(x, y)| {
+                    let neighbors = (-1..=1)
+                        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                        .filter(|(dx, dy)| {
+                            (*dx, *dy) != (0, 0) && cells.contains(&(x + dx, y + dy))
+                        })
+                        .count();
+                    rule.next(cells.contains(&(*x, *y)), neighbors)
+                })
+                .collect();
+        }
+
+        let mut universe = universe_with(&r_pentomino);
+        universe.step(7);
+        let mut expected: Vec<(i64, i64)> = cells.into_iter().collect();
+        expected.sort();
+        let mut live = universe.live_cells(-1000, -1000, 1000, 1000);
+        live.sort();
+        assert_eq!(live, expected);
+    }
+
+    #[test]
+    fn rejects_b0_rules() {
+        assert!(Universe::new("B0/S8".parse().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::{pattern, Model};
+use crate::cli_options::Args;
+
+// Runs the simulation without a window, for analysis jobs on machines without a GPU.
+// A CSV line per generation goes to stdout, the final state goes to `--output`
+// and the statistics to `--stats`.
+pub fn run(args: &Args) {
+    let mut model = Model::from_args(args);
+    // Nobody can undo here, and copying a large board every generation is not free.
+    model.set_history_len(0);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    writeln!(
+        out,
+        "generation,population,min_x,min_y,max_x,max_y,classification"
+    )
+    .unwrap();
+    write_stats(&mut out, &model);
+    for _ in 0..args.generations {
+        model.step();
+        write_stats(&mut out, &model);
+    }
+
+    let path = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("game_of_life.rle"));
+    // Both are tried, and a failure of either fails the run for the scripts calling it.
+    let mut failed = false;
+    match pattern::save(&path, &model.live_cells(), &model.rule) {
+        Ok(_) => eprintln!("Final state saved to {}", path.display()),
+        Err(e) => {
+            eprintln!("Cannot save final state {e}");
+            failed = true;
+        }
+    }
+    match model.stats.save(&args.stats) {
+        Ok(_) => eprintln!("Statistics saved to {}", args.stats.display()),
+        Err(e) => {
+            eprintln!("Cannot save statistics {e}");
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn write_stats(out: &mut impl Write, model: &Model) {
+    let bounds = match model.bounding_box() {
+        Some((x0, y0, x1, y1)) => format!("{x0},{y0},{x1},{y1}"),
+        None => ",,,".to_string(),
+    };
+    let classification = model
+        .detector
+        .result()
+        .map_or("".to_string(), |result| result.to_string());
+    writeln!(
+        out,
+        "{},{},{bounds},{classification}",
+        model.generation,
+        model.population()
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod test_headless {
+    use super::super::GridStatus;
+    use super::*;
+
+    #[test]
+    fn stats_lines() {
+        let mut model = Model::new(None);
+        model.detect = true;
+        let mut out = vec![];
+        write_stats(&mut out, &model);
+
+        // A blinker, standing then lying down.
+        for y in 4..7 {
+            model.grid[5][y] = GridStatus::LIFE;
+        }
+        write_stats(&mut out, &model);
+        model.step();
+        write_stats(&mut out, &model);
+        model.step();
+        write_stats(&mut out, &model);
+
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "0,0,,,,,\n0,3,5,4,5,6,\n1,3,4,5,6,5,\n2,3,5,4,5,6,oscillator p2\n"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+// Bounded undo and redo stacks of past states.
+// Once `capacity` states are kept, recording a new one forgets the oldest.
+#[derive(Clone, Debug)]
+pub struct History<T> {
+    past: VecDeque<T>,
+    future: Vec<T>,
+    capacity: usize,
+}
+
+impl<T> History<T> {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            past: VecDeque::with_capacity(capacity),
+            future: vec![],
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.past.len() > capacity {
+            self.past.pop_front();
+        }
+    }
+
+    // Remembers the state from before a change. Whatever was undone cannot be redone any more.
+    pub fn record(&mut self, before: T) {
+        self.future.clear();
+        if self.capacity == 0 {
+            return;
+        }
+        if self.past.len() == self.capacity {
+            self.past.pop_front();
+        }
+        self.past.push_back(before);
+    }
+
+    // State from before the last change, `current` is kept for `redo`.
+    pub fn undo(&mut self, current: T) -> Result<T, T> {
+        match self.past.pop_back() {
+            Some(before) => {
+                self.future.push(current);
+                Ok(before)
+            }
+            None => Err(current),
+        }
+    }
+
+    pub fn redo(&mut self, current: T) -> Result<T, T> {
+        match self.future.pop() {
+            Some(after) => {
+                self.past.push_back(current);
+                Ok(after)
+            }
+            None => Err(current),
+        }
+    }
+
+    // Forgets what was undone, once it cannot be reached any more.
+    pub fn clear_future(&mut self) {
+        self.future.clear();
+    }
+
+    // Forgets the states, past or undone, `keep` turns down.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        self.past.retain(&mut keep);
+        self.future.retain(keep);
+    }
+
+    // Every state kept, to be fixed up when what they refer to moves.
+    pub fn states_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.past.iter_mut().chain(self.future.iter_mut())
+    }
+}
+
+#[cfg(test)]
+mod test_history {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::new(3);
+        assert_eq!(history.undo(0), Err(0));
+
+        history.record(0);
+        history.record(1);
+        history.record(2);
+        assert_eq!(history.undo(3), Ok(2));
+        assert_eq!(history.redo(2), Ok(3));
+        assert_eq!(history.redo(3), Err(3));
+        assert_eq!(history.undo(3), Ok(2));
+        assert_eq!(history.states_mut().count(), 3);
+        history.retain(|state| *state < 3);
+        assert_eq!(history.states_mut().count(), 2);
+
+        // A new change drops the redo stack and the oldest state past the capacity.
+        history.record(2);
+        history.record(5);
+        assert_eq!(history.redo(6), Err(6));
+        assert_eq!(history.undo(6), Ok(5));
+        assert_eq!(history.undo(5), Ok(2));
+        assert_eq!(history.undo(2), Ok(1));
+        assert_eq!(history.undo(1), Err(1));
+
+        history.clear_future();
+        assert_eq!(history.redo(1), Err(1));
+    }
+}
@@ -0,0 +1,251 @@
+use super::{Grid, Topology};
+
+// Larger than Life: neighbours up to `range` cells away, and birth and survival
+// as ranges of neighbour counts.
+// https://conwaylife.com/wiki/Larger_than_Life
+//
+// Rules are written as in Golly, "R5,C0,M1,S34..58,B34..45,NM" being Bosco's rule:
+// - R the range, from 1 to 10;
+// - C the number of states, 0 or 2 for two, more for Generations;
+// - M1 counts the cell itself as a neighbour, M0 does not;
+// - S and B the survival and birth ranges;
+// - NM a Moore (square) neighbourhood, NN a von Neumann (diamond) one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Moore,
+    VonNeumann,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LargerThanLife {
+    pub range: usize,
+    pub shape: Shape,
+    pub middle: bool,
+    pub birth: (usize, usize),
+    pub survival: (usize, usize),
+}
+
+pub const MAX_RANGE: usize = 10;
+
+impl LargerThanLife {
+    // Cells counted as neighbours.
+    pub fn size(&self) -> usize {
+        let r = self.range;
+        let cells = match self.shape {
+            Shape::Moore => (2 * r + 1) * (2 * r + 1),
+            Shape::VonNeumann => 2 * r * (r + 1) + 1,
+        };
+        if self.middle {
+            cells
+        } else {
+            cells - 1
+        }
+    }
+
+    pub fn next(&self, alive: bool, neighbors: usize) -> bool {
+        let (min, max) = if alive { self.survival } else { self.birth };
+        (min..=max).contains(&neighbors)
+    }
+
+    // Live neighbours of every cell of the grid.
+    //
+    // Moore sums come from a summed-area table and von Neumann ones from prefix sums
+    // along the rows, so a cell costs the same at range 1 and 10, or 2R + 1 row lookups.
+    pub(super) fn counts(&self, grid: &Grid, topology: Topology) -> Vec<Vec<usize>> {
+        let (width, height) = (grid.len(), grid[0].len());
+        let r = self.range;
+        // The grid with `r` cells of margin all around, filled in by the topology.
+        let padded_width = width + 2 * r;
+        let padded_height = height + 2 * r;
+        let alive = |x: usize, y: usize| -> usize {
+            let x = x as isize - r as isize;
+            let y = y as isize - r as isize;
+            match topology.wrap(x, y, width, height) {
+                Some((x, y)) => grid[x][y].grid_value() as usize,
+                None => 0,
+            }
+        };
+
+        // sums[x][y] holds the live cells in [0, x) x [0, y) of the padded grid.
+        let mut sums = vec![vec![0; padded_height + 1]; padded_width + 1];
+        for x in 0..padded_width {
+            for y in 0..padded_height {
+                sums[x + 1][y + 1] = match self.shape {
+                    Shape::Moore => sums[x][y + 1] + sums[x + 1][y] - sums[x][y],
+                    Shape::VonNeumann => sums[x][y + 1],
+                } + alive(x, y);
+            }
+        }
+
+        let mut counts = vec![vec![0; height]; width];
+        for x in 0..width {
+            for y in 0..height {
+                // (x, y) sits at (x + r, y + r) in the padded grid.
+                let total = match self.shape {
+                    Shape::Moore => {
+                        let (x1, y1) = (x + 2 * r + 1, y + 2 * r + 1);
+                        sums[x1][y1] + sums[x][y] - sums[x][y1] - sums[x1][y]
+                    }
+                    Shape::VonNeumann => (0..=2 * r)
+                        .map(|row| {
+                            let reach = r - row.abs_diff(r);
+                            let (x0, x1) = (x + r - reach, x + r + reach + 1);
+                            sums[x1][y + row + 1] - sums[x0][y + row + 1]
+                        })
+                        .sum(),
+                };
+                counts[x][y] = match self.middle {
+                    true => total,
+                    false => total - grid[x][y].grid_value() as usize,
+                };
+            }
+        }
+        counts
+    }
+}
+
+// Parses the Golly notation, giving the rule and its number of states.
+pub fn parse(rule: &str) -> Result<(LargerThanLife, u8), String> {
+    let invalid = |part: &str| format!("invalid `{part}` in Larger than Life rule `{rule}`");
+    let (mut range, mut states, mut middle, mut shape) = (None, 2, false, Shape::Moore);
+    let (mut birth, mut survival) = (None, None);
+    for part in rule.split(',').map(str::trim) {
+        let number = |value: &str| value.parse::<usize>().map_err(|_| invalid(part));
+        let counts = |value: &str| match value.split_once("..") {
+            Some((min, max)) => Ok((number(min)?, number(max)?)),
+            None => number(value).map(|n| (n, n)),
+        };
+        match part.split_at(part.chars().next().map_or(0, char::len_utf8)) {
+            ("R", value) => range = Some(number(value)?),
+            ("C", value) => states = number(value)?.max(2),
+            ("M", "0") => middle = false,
+            ("M", "1") => middle = true,
+            ("S", value) => survival = Some(counts(value)?),
+            ("B", value) => birth = Some(counts(value)?),
+            ("N", "M") => shape = Shape::Moore,
+            ("N", "N") => shape = Shape::VonNeumann,
+            _ => return Err(invalid(part)),
+        }
+    }
+
+    let range = range.ok_or_else(|| format!("Larger than Life rule `{rule}` has no range"))?;
+    if !(1..=MAX_RANGE).contains(&range) {
+        return Err(format!(
+            "range {range} of `{rule}` is not between 1 and {MAX_RANGE}"
+        ));
+    }
+    let states = u8::try_from(states).map_err(|_| invalid(&format!("C{states}")))?;
+    let larger = LargerThanLife {
+        range,
+        shape,
+        middle,
+        birth: birth.ok_or_else(|| format!("Larger than Life rule `{rule}` has no births"))?,
+        survival: survival
+            .ok_or_else(|| format!("Larger than Life rule `{rule}` has no survivals"))?,
+    };
+    for (min, max) in [larger.birth, larger.survival] {
+        if min > max || max > larger.size() {
+            return Err(format!(
+                "counts {min}..{max} of `{rule}` are not within 0..{}",
+                larger.size()
+            ));
+        }
+    }
+    Ok((larger, states))
+}
+
+// Golly's notation of the rule with `states` states, the inverse of `parse`.
+pub fn rulestring(larger: &LargerThanLife, states: u8) -> String {
+    let states = if states > 2 { states } else { 0 };
+    let shape = match larger.shape {
+        Shape::Moore => "M",
+        Shape::VonNeumann => "N",
+    };
+    format!(
+        "R{},C{states},M{},S{}..{},B{}..{},N{shape}",
+        larger.range,
+        larger.middle as u8,
+        larger.survival.0,
+        larger.survival.1,
+        larger.birth.0,
+        larger.birth.1
+    )
+}
+
+#[cfg(test)]
+mod test_larger {
+    use super::super::{new_grid, GridStatus};
+    use super::*;
+
+    // The same counts, one neighbour at a time.
+    fn slow_counts(larger: &LargerThanLife, grid: &Grid, topology: Topology) -> Vec<Vec<usize>> {
+        let (width, height) = (grid.len(), grid[0].len());
+        let r = larger.range as isize;
+        let count = |x: usize, y: usize| {
+            let mut count = 0;
+            for dx in -r..=r {
+                for dy in -r..=r {
+                    let inside = match larger.shape {
+                        Shape::Moore => true,
+                        Shape::VonNeumann => dx.abs() + dy.abs() <= r,
+                    };
+                    if !inside || (!larger.middle && (dx, dy) == (0, 0)) {
+                        continue;
+                    }
+                    let (nx, ny) = (x as isize + dx, y as isize + dy);
+                    if let Some((nx, ny)) = topology.wrap(nx, ny, width, height) {
+                        count += grid[nx][ny].grid_value() as usize;
+                    }
+                }
+            }
+            count
+        };
+        (0..width)
+            .map(|x| (0..height).map(|y| count(x, y)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn table_sums_match_counting() {
+        let mut grid = new_grid(GridStatus::NOT, 24, 24);
+        for (x, col) in grid.iter_mut().enumerate() {
+            for (y, cell) in col.iter_mut().enumerate() {
+                if (x * 7 + y * 13) % 5 < 2 {
+                    *cell = GridStatus::LIFE;
+                }
+            }
+        }
+        for rule in [
+            "R1,C0,M0,S2..3,B3..3,NM",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "R3,C0,M0,S2..6,B3..5,NN",
+        ] {
+            let (larger, _) = parse(rule).unwrap();
+            for topology in [Topology::Bounded, Topology::Torus] {
+                assert_eq!(
+                    larger.counts(&grid, topology),
+                    slow_counts(&larger, &grid, topology),
+                    "{rule} {topology:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_rules() {
+        let (bosco, states) = parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+        assert_eq!(states, 2);
+        assert_eq!(bosco.size(), 121);
+        assert!(bosco.next(true, 58) && !bosco.next(true, 59));
+        assert!(bosco.next(false, 34) && !bosco.next(false, 33));
+
+        let (diamond, states) = parse("R2,C3,M0,S1..2,B3,NN").unwrap();
+        assert_eq!((diamond.size(), states), (12, 3));
+        assert_eq!(diamond.birth, (3, 3));
+
+        assert!(parse("R11,C0,M0,S2..3,B3..3,NM").is_err());
+        assert!(parse("R1,C0,M0,S2..3,B3..9,NM").is_err());
+        assert!(parse("R1,C0,M0,S2..3,NM").is_err());
+        assert!(parse("R1,C0,M2,S2..3,B3..3,NM").is_err());
+    }
+}
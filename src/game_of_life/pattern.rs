@@ -0,0 +1,592 @@
+use std::path::Path;
+
+use super::{Grid, GridStatus, Rule};
+
+// Patterns are stored in the same orientation as the model's grid:
+// `cells[x][y]` with `y = 0` on the bottom row.
+// Pattern files list rows from the top, so rows are flipped on the way in and out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub cells: Grid,
+    pub rule: Option<Rule>,
+}
+
+impl Pattern {
+    pub fn width(&self) -> usize {
+        self.cells.len()
+    }
+    pub fn height(&self) -> usize {
+        self.cells.first().map_or(0, |col| col.len())
+    }
+}
+
+// Builds a `width` x `height` pattern from rows listed top to bottom.
+fn from_rows(rows: &[Vec<GridStatus>], width: usize, height: usize) -> Grid {
+    let mut cells = vec![vec![GridStatus::NOT; height]; width];
+    for (row_i, row) in rows.iter().enumerate().take(height) {
+        for (x, cell) in row.iter().enumerate().take(width) {
+            cells[x][height - 1 - row_i] = *cell;
+        }
+    }
+    cells
+}
+
+// Smallest rectangle holding every live or dying cell, as (min_x, min_y, max_x, max_y).
+pub fn bounding_box(grid: &Grid) -> Option<(usize, usize, usize, usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (x, col) in grid.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if *cell == GridStatus::NOT {
+                continue;
+            }
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            });
+        }
+    }
+    bounds
+}
+
+// A live or dying cell as (x, y, status).
+pub type Cell = (i64, i64, GridStatus);
+
+// Live and dying cells of a grid. The writers take cells rather than a grid, so a
+// pattern spread over a huge area is saved without ever being made dense.
+pub fn cells(grid: &Grid) -> Vec<Cell> {
+    let mut cells = vec![];
+    for (x, col) in grid.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if *cell != GridStatus::NOT {
+                cells.push((x as i64, y as i64, *cell));
+            }
+        }
+    }
+    cells
+}
+
+// Cells sorted as the rows list them, top to bottom then left to right, with the corner
+// of their bounding box as (min_x, max_y).
+fn in_row_order(cells: &[Cell]) -> (Vec<Cell>, (i64, i64)) {
+    let mut sorted: Vec<_> = cells
+        .iter()
+        .filter(|(_, _, cell)| *cell != GridStatus::NOT)
+        .copied()
+        .collect();
+    sorted.sort_by_key(|(x, y, _)| (-y, *x));
+    let x0 = sorted.iter().map(|(x, _, _)| *x).min().unwrap_or(0);
+    let y1 = sorted.first().map_or(0, |(_, y, _)| *y);
+    (sorted, (x0, y1))
+}
+
+// Rows of the live area, listed top to bottom.
+fn to_rows(cells: &[Cell]) -> Vec<Vec<GridStatus>> {
+    let (cells, (x0, y1)) = in_row_order(cells);
+    let Some((_, y0, _)) = cells.last() else {
+        return vec![];
+    };
+    let x1 = cells.iter().map(|(x, _, _)| *x).max().unwrap();
+    let mut rows = vec![vec![GridStatus::NOT; (x1 - x0 + 1) as usize]; (y1 - y0 + 1) as usize];
+    for (x, y, cell) in cells {
+        rows[(y1 - y) as usize][(x - x0) as usize] = cell;
+    }
+    rows
+}
+
+// Run Length Encoded patterns.
+// https://conwaylife.com/wiki/Run_Length_Encoded
+//
+// #C comment lines
+// x = 3, y = 3, rule = B3/S23
+// bo$2bo$3o!
+//
+// Generations rules use `.` for dead, `A` for alive and `B`, `C`... for the dying states.
+// As in Golly, states past `X` (24) take two letters, `pA` to `pX` for 25 to 48, then
+// `qA` and so on up to `yO` for 255.
+pub fn read_rle(text: &str) -> Result<Pattern, String> {
+    let (cells, rule) = read_rle_cells(text)?;
+    Ok(Pattern {
+        cells,
+        rule: rule.map(|rule| rule.parse::<Rule>()).transpose()?,
+    })
+}
+
+// The cells of an RLE pattern and its rule as written, also for the multi-state rules
+// other than Generations, like Golly's WireWorld where the states are numbered the same.
+pub fn read_rle_cells(text: &str) -> Result<(Grid, Option<String>), String> {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let header = lines.next().ok_or("RLE pattern is empty")?;
+    let mut width = None;
+    let mut height = None;
+    let mut rule = None;
+    let mut fields = header;
+    while !fields.trim().is_empty() {
+        let (field, rest) = fields.split_once(',').unwrap_or((fields, ""));
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("invalid RLE header field `{field}`"))?;
+        let value = value.trim();
+        match key.trim() {
+            "x" => width = value.parse::<usize>().ok(),
+            "y" => height = value.parse::<usize>().ok(),
+            // The rule comes last and may hold commas itself, like Larger than Life rules.
+            "rule" => {
+                let (_, value) = fields.split_once('=').unwrap();
+                rule = Some(value.trim().to_string());
+                break;
+            }
+            _ => {}
+        }
+        fields = rest;
+    }
+    let width = width.ok_or("RLE header is missing `x`")?;
+    let height = height.ok_or("RLE header is missing `y`")?;
+
+    let mut rows: Vec<Vec<GridStatus>> = vec![vec![]];
+    let mut run_count = String::new();
+    // First letter of a two letter state.
+    let mut prefix = None;
+    'body: for line in lines {
+        for c in line.chars() {
+            if c.is_ascii_digit() && prefix.is_none() {
+                run_count.push(c);
+                continue;
+            }
+            if ('p'..='y').contains(&c) && prefix.is_none() {
+                prefix = Some(c);
+                continue;
+            }
+            let count = run_count.parse::<usize>().unwrap_or(1);
+            run_count.clear();
+            let cell = match (prefix.take(), c) {
+                (Some(prefix), 'A'..='X') => {
+                    let state = 24 * (prefix as u32 - 'p' as u32 + 1) + (c as u32 - 'A' as u32) + 1;
+                    u8::try_from(state)
+                        .map(GridStatus::from_state)
+                        .map_err(|_| format!("state `{prefix}{c}` in RLE body is above 255"))?
+                }
+                (Some(prefix), c) => return Err(format!("unexpected `{prefix}{c}` in RLE body")),
+                (None, '$') => {
+                    rows.extend(vec![vec![]; count]);
+                    continue;
+                }
+                (None, '!') => break 'body,
+                (None, c) if c.is_whitespace() => continue,
+                (None, 'b' | '.') => GridStatus::NOT,
+                (None, 'o' | 'A') => GridStatus::LIFE,
+                (None, 'B'..='X') => GridStatus::from_state(c as u8 - b'A' + 1),
+                (None, c) => return Err(format!("unexpected `{c}` in RLE body")),
+            };
+            rows.last_mut().unwrap().extend(vec![cell; count]);
+        }
+    }
+
+    Ok((from_rows(&rows, width, height), rule))
+}
+
+pub fn write_rle(cells: &[Cell], rule: &Rule) -> String {
+    let (cells, (x0, y1)) = in_row_order(cells);
+    let width = cells.iter().map(|(x, _, _)| x - x0 + 1).max().unwrap_or(0);
+    let height = cells.last().map_or(0, |(_, y, _)| y1 - y + 1);
+    let mut text = format!("x = {width}, y = {height}, rule = {rule}\n");
+
+    let generations = rule.states() > 2;
+    let tag = |cell: &GridStatus| match (cell, generations) {
+        (GridStatus::NOT, false) => "b".to_string(),
+        (GridStatus::NOT, true) => ".".to_string(),
+        (GridStatus::LIFE, false) => "o".to_string(),
+        (GridStatus::LIFE, true) => "A".to_string(),
+        (dying, _) => state_letters(dying.state() as u32),
+    };
+
+    // Pairs of (count, tag) before wrapping into lines.
+    let mut runs: Vec<(usize, String)> = vec![];
+    let mut push = |count: usize, tag: String| match runs.last_mut() {
+        Some((last_count, last_tag)) if *last_tag == tag => *last_count += count,
+        _ => runs.push((count, tag)),
+    };
+    // Dead cells before a live one and rows without any are runs, whatever their length.
+    // Trailing dead cells are implied by the end of the row.
+    let (mut row, mut next_x) = (y1, x0);
+    for (x, y, cell) in &cells {
+        if *y < row {
+            push((row - y) as usize, "$".to_string());
+            (row, next_x) = (*y, x0);
+        }
+        if *x > next_x {
+            push((x - next_x) as usize, tag(&GridStatus::NOT));
+        }
+        push(1, tag(cell));
+        next_x = x + 1;
+    }
+    push(1, "!".to_string());
+
+    // Lines should not exceed 70 characters.
+    let mut line = String::new();
+    for (count, tag) in runs {
+        let run = match count {
+            1 => tag,
+            n => format!("{n}{tag}"),
+        };
+        if line.len() + run.len() > 70 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+// Letters of a state from 1 to 255 in the RLE body of a multi-state rule.
+fn state_letters(state: u32) -> String {
+    let letter = |n: u32| char::from(b'A' + n as u8);
+    match state {
+        1..=24 => letter(state - 1).to_string(),
+        _ => {
+            let prefix = char::from(b'p' + ((state - 25) / 24) as u8);
+            format!("{prefix}{}", letter((state - 25) % 24))
+        }
+    }
+}
+
+// Plaintext patterns.
+// https://conwaylife.com/wiki/Plaintext
+//
+// !Name: Glider
+// .O.
+// ..O
+// OOO
+pub fn read_plaintext(text: &str) -> Result<Pattern, String> {
+    let mut rows = vec![];
+    for line in text.lines().map(str::trim_end) {
+        if line.starts_with('!') {
+            continue;
+        }
+        let row = line
+            .chars()
+            .map(|c| match c {
+                '.' => Ok(GridStatus::NOT),
+                'O' | '*' => Ok(GridStatus::LIFE),
+                c => Err(format!("unexpected `{c}` in plaintext pattern")),
+            })
+            .collect::<Result<Vec<GridStatus>, String>>()?;
+        rows.push(row);
+    }
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let height = rows.len();
+    Ok(Pattern {
+        cells: from_rows(&rows, width, height),
+        rule: None,
+    })
+}
+
+pub fn write_plaintext(cells: &[Cell]) -> String {
+    let mut text = String::from("!Name: game_of_life\n");
+    for row in to_rows(cells) {
+        let line: String = row
+            .iter()
+            .map(|cell| match cell {
+                GridStatus::LIFE => 'O',
+                _ => '.',
+            })
+            .collect();
+        text.push_str(line.trim_end_matches('.'));
+        text.push('\n');
+    }
+    text
+}
+
+// Life 1.06 patterns, one live cell per line with `y` growing downwards.
+// https://conwaylife.com/wiki/Life_1.06
+//
+// #Life 1.06
+// 0 -1
+// 1 0
+// -1 1
+pub fn read_life106(text: &str) -> Result<Pattern, String> {
+    let mut points = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let coordinates = line
+            .split_whitespace()
+            .map(|n| n.parse::<isize>())
+            .collect::<Result<Vec<isize>, _>>()
+            .map_err(|e| format!("invalid Life 1.06 line `{line}`: {e}"))?;
+        match coordinates[..] {
+            [x, y] => points.push((x, y)),
+            _ => return Err(format!("invalid Life 1.06 line `{line}`")),
+        }
+    }
+
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+    let width = points.iter().map(|p| p.0 - min_x + 1).max().unwrap_or(0) as usize;
+    let height = points.iter().map(|p| p.1 - min_y + 1).max().unwrap_or(0) as usize;
+    let mut rows = vec![vec![GridStatus::NOT; width]; height];
+    for (x, y) in points {
+        rows[(y - min_y) as usize][(x - min_x) as usize] = GridStatus::LIFE;
+    }
+    Ok(Pattern {
+        cells: from_rows(&rows, width, height),
+        rule: None,
+    })
+}
+
+pub fn write_life106(cells: &[Cell]) -> String {
+    let mut text = String::from("#Life 1.06\n");
+    let (cells, (x0, y1)) = in_row_order(cells);
+    for (x, y, cell) in cells {
+        if cell == GridStatus::LIFE {
+            text.push_str(&format!("{} {}\n", x - x0, y1 - y));
+        }
+    }
+    text
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Rle,
+    Plaintext,
+    Life106,
+}
+
+impl Format {
+    // Picks the format from the file extension, RLE when unknown.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("cells") => Format::Plaintext,
+            Some("lif") | Some("life") => Format::Life106,
+            _ => Format::Rle,
+        }
+    }
+    pub fn read(&self, text: &str) -> Result<Pattern, String> {
+        match self {
+            Format::Rle => read_rle(text),
+            Format::Plaintext => read_plaintext(text),
+            Format::Life106 => read_life106(text),
+        }
+    }
+    pub fn write(&self, cells: &[Cell], rule: &Rule) -> String {
+        match self {
+            Format::Rle => write_rle(cells, rule),
+            Format::Plaintext => write_plaintext(cells),
+            Format::Life106 => write_life106(cells),
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Pattern, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Format::from_path(path).read(&text)
+}
+
+pub fn save(path: &Path, cells: &[Cell], rule: &Rule) -> Result<(), String> {
+    let text = Format::from_path(path).write(cells, rule);
+    std::fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+// Copies the pattern onto the middle of the grid, clipping what falls outside.
+pub fn place_centered(grid: &mut Grid, pattern: &Pattern) {
+    let grid_width = grid.len() as isize;
+    let grid_height = grid.first().map_or(0, |col| col.len()) as isize;
+    let offset_x = (grid_width - pattern.width() as isize) / 2;
+    let offset_y = (grid_height - pattern.height() as isize) / 2;
+    place_cells(grid, &pattern.cells, (offset_x, offset_y));
+}
+
+// Same for the `cells[x][y]` of any kind of grid, like Wireworld's.
+pub fn place_cells_centered<T: Copy>(grid: &mut [Vec<T>], cells: &[Vec<T>]) {
+    let grid_width = grid.len() as isize;
+    let grid_height = grid.first().map_or(0, |col| col.len()) as isize;
+    let offset_x = (grid_width - cells.len() as isize) / 2;
+    let offset_y = (grid_height - cells.first().map_or(0, |col| col.len()) as isize) / 2;
+    place_cells(grid, cells, (offset_x, offset_y));
+}
+
+fn place_cells<T: Copy>(
+    grid: &mut [Vec<T>],
+    cells: &[Vec<T>],
+    (offset_x, offset_y): (isize, isize),
+) {
+    let grid_width = grid.len() as isize;
+    let grid_height = grid.first().map_or(0, |col| col.len()) as isize;
+    for (x, col) in cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            let grid_x = x as isize + offset_x;
+            let grid_y = y as isize + offset_y;
+            if grid_x < 0 || grid_x >= grid_width || grid_y < 0 || grid_y >= grid_height {
+                continue;
+            }
+            grid[grid_x as usize][grid_y as usize] = *cell;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_rle {
+    use super::super::new_grid;
+    use super::*;
+
+    const GLIDER: &str = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!\n";
+
+    #[test]
+    fn read_glider() {
+        let pattern = read_rle(GLIDER).unwrap();
+        assert_eq!(pattern.rule, Some(Rule::default()));
+        assert_eq!((pattern.width(), pattern.height()), (3, 3));
+
+        // Top row `bob` ends up at y = 2.
+        assert_eq!(pattern.cells[1][2], GridStatus::LIFE);
+        assert_eq!(pattern.cells[0][2], GridStatus::NOT);
+        assert_eq!(pattern.cells[2][1], GridStatus::LIFE);
+        for x in 0..3 {
+            assert_eq!(pattern.cells[x][0], GridStatus::LIFE);
+        }
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let mut grid = new_grid(GridStatus::NOT, 10, 10);
+        place_centered(&mut grid, &read_rle(GLIDER).unwrap());
+
+        let text = write_rle(&cells(&grid), &Rule::default());
+        assert_eq!(text, "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n");
+
+        let pattern = read_rle(&text).unwrap();
+        assert_eq!(pattern, read_rle(GLIDER).unwrap());
+    }
+
+    #[test]
+    fn generations_round_trip() {
+        let text = "x = 3, y = 2, rule = B2/S/C3\n.AB$2BA!\n";
+        let pattern = read_rle(text).unwrap();
+        assert_eq!(pattern.rule.map(|rule| rule.states()), Some(3));
+        assert_eq!(pattern.cells[1][1], GridStatus::LIFE);
+        assert_eq!(pattern.cells[2][1], GridStatus::DYING(1));
+        assert_eq!(pattern.cells[0][0], GridStatus::DYING(1));
+        assert_eq!(
+            write_rle(&cells(&pattern.cells), &pattern.rule.unwrap()),
+            text
+        );
+    }
+
+    #[test]
+    fn two_letter_states() {
+        assert_eq!(state_letters(24), "X");
+        assert_eq!(state_letters(25), "pA");
+        assert_eq!(state_letters(48), "pX");
+        assert_eq!(state_letters(49), "qA");
+        assert_eq!(state_letters(255), "yO");
+
+        let text = "x = 5, y = 1, rule = B2/S/C255\nA3pAyO!\n";
+        let pattern = read_rle(text).unwrap();
+        assert_eq!(pattern.cells[1][0], GridStatus::DYING(24));
+        assert_eq!(pattern.cells[4][0], GridStatus::DYING(254));
+        assert_eq!(
+            write_rle(&cells(&pattern.cells), &pattern.rule.unwrap()),
+            text
+        );
+        assert!(read_rle("x = 1, y = 1\nyP!").is_err());
+        assert!(read_rle("x = 1, y = 1\npb!").is_err());
+    }
+
+    #[test]
+    fn header_keys() {
+        let text = "x=2,y=1,overrule = 1 , rule=B36/S23\n2o!\n";
+        let pattern = read_rle(text).unwrap();
+        assert_eq!(pattern.rule, Some(Rule::HIGH_LIFE.parse().unwrap()));
+        assert_eq!(pattern.cells.len(), 2);
+        assert!(read_rle("x = 2, y\n2o!\n").is_err());
+    }
+
+    #[test]
+    fn rule_with_commas() {
+        let text = "x = 2, y = 1, rule = R5,C0,M1,S34..58,B34..45,NM\n2o!\n";
+        let pattern = read_rle(text).unwrap();
+        assert_eq!(pattern.rule, Some(Rule::BOSCO.parse().unwrap()));
+        assert_eq!(pattern.width(), 2);
+        assert_eq!(
+            write_rle(&cells(&pattern.cells), &pattern.rule.unwrap()),
+            text
+        );
+    }
+
+    #[test]
+    fn empty_rows_and_long_lines() {
+        let text = "x = 80, y = 4\n80o3$80o!";
+        let pattern = read_rle(text).unwrap();
+        assert_eq!(pattern.cells[79][3], GridStatus::LIFE);
+        assert_eq!(pattern.cells[79][1], GridStatus::NOT);
+        assert_eq!(pattern.cells[0][0], GridStatus::LIFE);
+
+        let written = write_rle(&cells(&pattern.cells), &Rule::default());
+        assert!(written.lines().all(|line| line.len() <= 70));
+        assert_eq!(read_rle(&written).unwrap().cells, pattern.cells);
+
+        // Cells far apart are written as long runs, without a grid between them.
+        let far = [
+            (-1_000_000_000, 0, GridStatus::LIFE),
+            (1_000_000_000, -5, GridStatus::LIFE),
+        ];
+        let text = write_rle(&far, &Rule::default());
+        assert_eq!(
+            text,
+            "x = 2000000001, y = 6, rule = B3/S23\no5$2000000000bo!\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_codecs {
+    use super::super::new_grid;
+    use super::*;
+
+    const GLIDER_CELLS: &str = "!Name: Glider\n.O\n..O\nOOO\n";
+    const GLIDER_LIFE106: &str = "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+
+    fn glider_grid() -> Grid {
+        let mut grid = new_grid(GridStatus::NOT, 8, 8);
+        for (x, y) in [(3, 4), (4, 3), (2, 2), (3, 2), (4, 2)] {
+            grid[x][y] = GridStatus::LIFE;
+        }
+        grid
+    }
+
+    #[test]
+    fn formats_read_the_same_glider() {
+        let rle = read_rle("x = 3, y = 3\nbo$2bo$3o!").unwrap();
+        let cells = read_plaintext(GLIDER_CELLS).unwrap();
+        let life106 = read_life106(GLIDER_LIFE106).unwrap();
+        assert_eq!(cells.cells, rle.cells);
+        assert_eq!(life106.cells, rle.cells);
+    }
+
+    #[test]
+    fn grid_round_trip() {
+        let grid = glider_grid();
+        for format in [Format::Rle, Format::Plaintext, Format::Life106] {
+            let text = format.write(&cells(&grid), &Rule::default());
+            let mut loaded = new_grid(GridStatus::NOT, 8, 8);
+            place_centered(&mut loaded, &format.read(&text).unwrap());
+            assert_eq!(loaded, grid, "{format:?}\n{text}");
+        }
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(Format::from_path(Path::new("a.cells")), Format::Plaintext);
+        assert_eq!(Format::from_path(Path::new("a.lif")), Format::Life106);
+        assert_eq!(Format::from_path(Path::new("a.rle")), Format::Rle);
+        assert!(read_plaintext("..X").is_err());
+        assert!(read_life106("#Life 1.06\n1").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::larger::{self, LargerThanLife};
+use super::tiling::Tiling;
+
+// Life-like rules in B/S notation.
+// https://conwaylife.com/wiki/Rulestring
+//
+// "B3/S23" reads as: a dead cell is Born with 3 neighbours,
+// a live cell Survives with 2 or 3 neighbours.
+// The older S/B form ("23/3") is accepted as well,
+// and so are a few names: conway, highlife, seeds, daynight, briansbrain, starwars, bosco.
+//
+// Generations rules add a third part with the number of states,
+// "B2/S/C3" or "/2/3" (Brian's Brain): live cells that do not survive
+// go through `states - 2` dying states before they are dead.
+// https://conwaylife.com/wiki/Generations
+//
+// A final H makes a hexagonal rule with up to 6 neighbours, "B2/S34H",
+// and a final T a triangular one with up to 12, where 10 to 12 are written A to C.
+//
+// Larger than Life rules, "R5,C0,M1,S34..58,B34..45,NM", count further neighbours
+// and replace `birth` and `survival` with ranges, see `larger`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    birth: [bool; 13],
+    survival: [bool; 13],
+    states: u8,
+    tiling: Tiling,
+    larger: Option<LargerThanLife>,
+}
+
+impl Rule {
+    pub const CONWAY: &'static str = "B3/S23";
+    pub const HIGH_LIFE: &'static str = "B36/S23";
+    pub const SEEDS: &'static str = "B2/S";
+    pub const DAY_AND_NIGHT: &'static str = "B3678/S34678";
+    pub const BRIANS_BRAIN: &'static str = "B2/S/C3";
+    pub const STAR_WARS: &'static str = "B2/S345/C4";
+    pub const BOSCO: &'static str = "R5,C0,M1,S34..58,B34..45,NM";
+
+    // Next state of a cell given its own state and the count of live neighbours.
+    pub fn next(&self, alive: bool, neighbors: usize) -> bool {
+        if let Some(larger) = &self.larger {
+            return larger.next(alive, neighbors);
+        }
+        if alive {
+            self.survival[neighbors]
+        } else {
+            self.birth[neighbors]
+        }
+    }
+
+    // Cell states, 2 (alive or dead) for life-like rules.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    pub fn tiling(&self) -> Tiling {
+        self.tiling
+    }
+
+    pub fn larger(&self) -> Option<&LargerThanLife> {
+        self.larger.as_ref()
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::from_str(Rule::CONWAY).unwrap()
+    }
+}
+
+fn parse_counts(digits: &str, tiling: Tiling) -> Result<[bool; 13], String> {
+    let mut counts = [false; 13];
+    for c in digits.chars() {
+        match c.to_digit(16) {
+            Some(n) if n as usize <= tiling.max_neighbors() => counts[n as usize] = true,
+            _ => return Err(format!("invalid neighbour count `{c}` in `{digits}`")),
+        }
+    }
+    Ok(counts)
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = match s.trim().to_lowercase().as_str() {
+            "conway" | "life" => Rule::CONWAY.to_string(),
+            "highlife" => Rule::HIGH_LIFE.to_string(),
+            "seeds" => Rule::SEEDS.to_string(),
+            "daynight" => Rule::DAY_AND_NIGHT.to_string(),
+            "briansbrain" => Rule::BRIANS_BRAIN.to_string(),
+            "starwars" => Rule::STAR_WARS.to_string(),
+            "bosco" => Rule::BOSCO.to_string(),
+            _ => s.trim().to_uppercase(),
+        };
+        if rule.starts_with('R') {
+            let (larger, states) = larger::parse(&rule)?;
+            return Ok(Rule {
+                birth: [false; 13],
+                survival: [false; 13],
+                states,
+                tiling: Tiling::Square,
+                larger: Some(larger),
+            });
+        }
+        let (rule, tiling) = match rule.strip_suffix('H') {
+            Some(rule) => (rule.to_string(), Tiling::Hex),
+            None => match rule.strip_suffix('T') {
+                Some(rule) => (rule.to_string(), Tiling::Triangle),
+                None => (rule, Tiling::Square),
+            },
+        };
+        let (rule, states) = match rule.splitn(3, '/').collect::<Vec<_>>()[..] {
+            [_, _, part] => {
+                let states = part
+                    .trim_start_matches(['C', 'G'])
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|states| *states >= 2)
+                    .ok_or_else(|| format!("invalid number of states `{part}` in `{s}`"))?;
+                (&rule[..rule.len() - part.len() - 1], states)
+            }
+            _ => (rule.as_str(), 2),
+        };
+        let (left, right) = rule
+            .split_once('/')
+            .ok_or_else(|| format!("rule `{s}` must have the form B../S.."))?;
+
+        let (birth, survival) = match (left.strip_prefix('B'), right.strip_prefix('S')) {
+            (Some(birth), Some(survival)) => (birth, survival),
+            _ => match (left.strip_prefix('S'), right.strip_prefix('B')) {
+                (Some(survival), Some(birth)) => (birth, survival),
+                // Legacy "S/B" form without letters, e.g. "23/3".
+                _ => (right, left),
+            },
+        };
+
+        Ok(Rule {
+            birth: parse_counts(birth, tiling)?,
+            survival: parse_counts(survival, tiling)?,
+            states,
+            tiling,
+            larger: None,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(larger) = &self.larger {
+            return write!(f, "{}", larger::rulestring(larger, self.states));
+        }
+        let digits = |counts: &[bool; 13]| -> String {
+            (0..13)
+                .filter(|n| counts[*n])
+                .map(|n| char::from_digit(n as u32, 16).unwrap().to_ascii_uppercase())
+                .collect()
+        };
+        write!(f, "B{}/S{}", digits(&self.birth), digits(&self.survival))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        write!(f, "{}", self.tiling.suffix())
+    }
+}
+
+#[cfg(test)]
+mod test_rule {
+    use super::*;
+
+    #[test]
+    fn parse_rulestrings() {
+        let conway = Rule::from_str("B3/S23").unwrap();
+        assert_eq!(conway, Rule::default());
+        assert_eq!(Rule::from_str("23/3").unwrap(), conway);
+        assert_eq!(Rule::from_str("s23/b3").unwrap(), conway);
+        assert_eq!(
+            Rule::from_str("HighLife").unwrap(),
+            Rule::from_str(Rule::HIGH_LIFE).unwrap()
+        );
+
+        let seeds = Rule::from_str(Rule::SEEDS).unwrap();
+        assert!(seeds.next(false, 2));
+        assert!(!seeds.next(true, 2));
+
+        assert!(Rule::from_str("B9/S23").is_err());
+        assert!(Rule::from_str("B3S23").is_err());
+    }
+
+    #[test]
+    fn parse_tilings() {
+        let hex = Rule::from_str("b2/s34h").unwrap();
+        assert_eq!(hex.tiling(), Tiling::Hex);
+        assert!(hex.next(true, 4));
+        assert!(Rule::from_str("B7/S34H").is_err());
+
+        let triangle = Rule::from_str("B4/S4567AT").unwrap();
+        assert_eq!(triangle.tiling(), Tiling::Triangle);
+        assert!(triangle.next(true, 10));
+        assert!(!triangle.next(true, 8));
+        assert!(Rule::from_str("B4A/S23").is_err());
+        assert_eq!(Rule::default().tiling(), Tiling::Square);
+    }
+
+    #[test]
+    fn parse_larger_than_life() {
+        let bosco = Rule::from_str("bosco").unwrap();
+        assert_eq!(bosco, Rule::from_str(Rule::BOSCO).unwrap());
+        assert_eq!(bosco.larger().map(|larger| larger.range), Some(5));
+        assert!(bosco.next(true, 40));
+        assert!(!bosco.next(false, 50));
+
+        let generations = Rule::from_str("r2,c3,m0,s1..2,b3..3,nn").unwrap();
+        assert_eq!(generations.states(), 3);
+        assert!(Rule::from_str("R0,C0,M0,S2..3,B3..3,NM").is_err());
+    }
+
+    #[test]
+    fn parse_generations() {
+        let brians_brain = Rule::from_str("/2/3").unwrap();
+        assert_eq!(brians_brain, Rule::from_str(Rule::BRIANS_BRAIN).unwrap());
+        assert_eq!(brians_brain.states(), 3);
+        assert!(brians_brain.next(false, 2));
+        assert!(!brians_brain.next(true, 2));
+
+        let star_wars = Rule::from_str("345/2/4").unwrap();
+        assert_eq!(star_wars, Rule::from_str("StarWars").unwrap());
+        assert_eq!(star_wars.states(), 4);
+        assert!(star_wars.next(true, 5));
+        assert_eq!(Rule::from_str("B2/S345/4").unwrap(), star_wars);
+
+        assert_eq!(Rule::default().states(), 2);
+        assert!(Rule::from_str("B2/S/C1").is_err());
+        assert!(Rule::from_str("B2/S/Cx").is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for rule in [
+            Rule::CONWAY,
+            Rule::HIGH_LIFE,
+            Rule::SEEDS,
+            Rule::DAY_AND_NIGHT,
+            Rule::BRIANS_BRAIN,
+            Rule::STAR_WARS,
+            "B2/S34H",
+            "B4/S4567ABT",
+            "B2/S/C3H",
+            Rule::BOSCO,
+            "R2,C3,M0,S1..2,B3..3,NN",
+        ] {
+            assert_eq!(Rule::from_str(rule).unwrap().to_string(), rule);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use super::camera::Camera;
+use super::{Engine, GridStatus, Topology};
+
+// Everything needed to pick a sketch up where it was left: the cells, where the
+// simulation is at and the settings it runs with. Saved with `W`, loaded with `--load`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub rule: String,
+    pub topology: Topology,
+    pub engine: Engine,
+    pub step_exponent: u8,
+    // Cells per side of the bit grid board.
+    pub board_size: Option<usize>,
+    pub generation: u64,
+    pub running: bool,
+    pub speed: f64,
+    pub camera: Camera,
+    pub seed: Option<u64>,
+    pub gradient: String,
+    pub trail: u32,
+    // Whether still lifes, oscillators and spaceships are looked for.
+    #[serde(default)]
+    pub detect: bool,
+    // Columns and rows of the window grid, the default window grid when missing.
+    #[serde(default)]
+    pub grid_size: Option<(usize, usize)>,
+    // Cell size the grid follows the window size with, `None` for a fixed grid.
+    #[serde(default)]
+    pub fit_cell_size: Option<f32>,
+    // Live and dying cells in world coordinates.
+    pub cells: Vec<(i64, i64, GridStatus)>,
+}
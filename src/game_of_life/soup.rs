@@ -0,0 +1,125 @@
+use clap::ValueEnum;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
+
+use super::{Grid, GridStatus};
+
+// Symmetries a soup can be drawn with, named like apgsearch does.
+// https://conwaylife.com/wiki/Symmetric_soup
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Symmetry {
+    // Every cell drawn on its own.
+    #[default]
+    None,
+    // Unchanged by a half turn.
+    C2,
+    // Unchanged by a quarter turn.
+    C4,
+    // Unchanged by quarter turns and mirroring.
+    D8,
+}
+
+impl Symmetry {
+    // Cells that must match (x, y) in a `size` x `size` square, (x, y) included.
+    fn orbit(&self, x: usize, y: usize, size: usize) -> Vec<(usize, usize)> {
+        let last = size - 1;
+        let quarter_turn = |(x, y): (usize, usize)| (y, last - x);
+        let half_turn = quarter_turn(quarter_turn((x, y)));
+        match self {
+            Symmetry::None => vec![(x, y)],
+            Symmetry::C2 => vec![(x, y), half_turn],
+            Symmetry::C4 | Symmetry::D8 => {
+                let mut orbit = vec![(x, y)];
+                for _ in 0..3 {
+                    orbit.push(quarter_turn(*orbit.last().unwrap()));
+                }
+                if *self == Symmetry::D8 {
+                    let mirrored: Vec<_> = orbit.iter().map(|(x, y)| (last - x, *y)).collect();
+                    orbit.extend(mirrored);
+                }
+                orbit
+            }
+        }
+    }
+}
+
+// A `size` x `size` square of random cells, each alive with probability `density`.
+// The same seed always gives the same soup.
+pub fn random_soup(
+    size: usize,
+    density: f64,
+    symmetry: Symmetry,
+    seed: u64,
+) -> Result<Grid, String> {
+    if !(0.0..=1.0).contains(&density) {
+        return Err(format!("soup density {density} is not between 0 and 1"));
+    }
+    if size == 0 {
+        return Err("soup size cannot be 0".to_string());
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut drawn = vec![vec![false; size]; size];
+    let mut soup = vec![vec![GridStatus::NOT; size]; size];
+    for x in 0..size {
+        for y in 0..size {
+            if drawn[x][y] {
+                continue;
+            }
+            let status = match rng.gen_bool(density) {
+                true => GridStatus::LIFE,
+                false => GridStatus::NOT,
+            };
+            for (x, y) in symmetry.orbit(x, y, size) {
+                drawn[x][y] = true;
+                soup[x][y] = status;
+            }
+        }
+    }
+    Ok(soup)
+}
+
+#[cfg(test)]
+mod test_soup {
+    use super::*;
+
+    fn alive(soup: &Grid) -> usize {
+        soup.iter()
+            .flatten()
+            .filter(|cell| **cell == GridStatus::LIFE)
+            .count()
+    }
+
+    #[test]
+    fn reproducible_from_the_seed() {
+        let soup = random_soup(16, 0.5, Symmetry::None, 42).unwrap();
+        assert_eq!(random_soup(16, 0.5, Symmetry::None, 42).unwrap(), soup);
+        assert_ne!(random_soup(16, 0.5, Symmetry::None, 43).unwrap(), soup);
+        assert_eq!(alive(&random_soup(16, 0.0, Symmetry::None, 42).unwrap()), 0);
+        assert_eq!(
+            alive(&random_soup(16, 1.0, Symmetry::None, 42).unwrap()),
+            256
+        );
+        assert!(random_soup(16, 1.5, Symmetry::None, 42).is_err());
+    }
+
+    #[test]
+    fn symmetric_soups() {
+        for size in [15, 16] {
+            let last = size - 1;
+            let c2 = random_soup(size, 0.5, Symmetry::C2, 7).unwrap();
+            let c4 = random_soup(size, 0.5, Symmetry::C4, 7).unwrap();
+            let d8 = random_soup(size, 0.5, Symmetry::D8, 7).unwrap();
+            for x in 0..size {
+                for y in 0..size {
+                    assert_eq!(c2[x][y], c2[last - x][last - y]);
+                    assert_eq!(c4[x][y], c4[y][last - x]);
+                    assert_eq!(d8[x][y], d8[y][last - x]);
+                    assert_eq!(d8[x][y], d8[last - x][y]);
+                }
+            }
+            // Only C1 has no reason to be symmetric.
+            let c1 = random_soup(size, 0.5, Symmetry::None, 7).unwrap();
+            assert_ne!(c1, c2);
+        }
+    }
+}
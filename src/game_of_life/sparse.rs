@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use super::tiling::Tiling;
+use super::{GridStatus, Rule};
+
+// Only the live and dying cells, in a hash map with no edges:
+// the universe grows with the pattern and its cost with the population.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sparse {
+    cells: HashMap<(i64, i64), GridStatus>,
+}
+
+impl Sparse {
+    pub fn new(rule: Rule) -> Result<Self, String> {
+        // Births in empty space would fill the infinite plane.
+        if rule.next(false, 0) {
+            return Err(format!(
+                "the sparse universe cannot run B0 rules like {rule}"
+            ));
+        }
+        if rule.tiling() != Tiling::Square || rule.larger().is_some() {
+            return Err(format!(
+                "the sparse universe only runs square cells and their eight neighbours, not {rule}"
+            ));
+        }
+        Ok(Sparse::default())
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> GridStatus {
+        self.cells.get(&(x, y)).copied().unwrap_or(GridStatus::NOT)
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, status: GridStatus) {
+        match status {
+            GridStatus::NOT => self.cells.remove(&(x, y)),
+            status => self.cells.insert((x, y), status),
+        };
+    }
+
+    pub fn population(&self) -> u64 {
+        self.cells
+            .values()
+            .filter(|status| **status == GridStatus::LIFE)
+            .count() as u64
+    }
+
+    // Live and dying cells, in no particular order.
+    pub fn cells(&self) -> impl Iterator<Item = (i64, i64, GridStatus)> + '_ {
+        self.cells.iter().map(|((x, y), status)| (*x, *y, *status))
+    }
+
+    // Smallest rectangle holding every live or dying cell, as (min_x, min_y, max_x, max_y).
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        self.cells.keys().fold(None, |bounds, (x, y)| {
+            Some(match bounds {
+                None => (*x, *y, *x, *y),
+                Some((x0, y0, x1, y1)) => (x0.min(*x), y0.min(*y), x1.max(*x), y1.max(*y)),
+            })
+        })
+    }
+
+    // Advances one generation, returning how many cells were born and how many died.
+    pub fn step(&mut self, rule: &Rule) -> (u64, u64) {
+        // Only cells next to a live one can be born.
+        let mut live_neighbors: HashMap<(i64, i64), usize> = HashMap::new();
+        for ((x, y), status) in self.cells.iter() {
+            if *status != GridStatus::LIFE {
+                continue;
+            }
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if (dx, dy) != (0, 0) {
+                        *live_neighbors.entry((x + dx, y + dy)).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        let mut next = HashMap::new();
+        let (mut births, mut deaths) = (0, 0);
+        for (position, status) in self.cells.iter() {
+            let neighbors = live_neighbors.get(position).copied().unwrap_or(0);
+            let next_status = status.next(neighbors, rule);
+            match (*status == GridStatus::LIFE, next_status == GridStatus::LIFE) {
+                (false, true) => births += 1,
+                (true, false) => deaths += 1,
+                _ => {}
+            }
+            if next_status != GridStatus::NOT {
+                next.insert(*position, next_status);
+            }
+        }
+        for (position, neighbors) in live_neighbors {
+            if !self.cells.contains_key(&position) && rule.next(false, neighbors) {
+                next.insert(position, GridStatus::LIFE);
+                births += 1;
+            }
+        }
+        self.cells = next;
+        (births, deaths)
+    }
+}
+
+#[cfg(test)]
+mod test_sparse {
+    use super::*;
+
+    #[test]
+    fn glider_travels_without_edges() {
+        let mut universe = Sparse::new(Rule::default()).unwrap();
+        for (x, y) in [(1, 2), (2, 1), (0, 0), (1, 0), (2, 0)] {
+            universe.set(x, y, GridStatus::LIFE);
+        }
+        for _ in 0..400 {
+            universe.step(&Rule::default());
+        }
+        assert_eq!(universe.population(), 5);
+        assert_eq!(universe.bounding_box(), Some((100, -100, 102, -98)));
+        assert!(Sparse::new("B03/S23".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn generations_rules() {
+        // Brian's Brain: two cells give birth to two more, and die.
+        let rule: Rule = Rule::BRIANS_BRAIN.parse().unwrap();
+        let mut universe = Sparse::new(rule).unwrap();
+        universe.set(0, 0, GridStatus::LIFE);
+        universe.set(0, 1, GridStatus::LIFE);
+        universe.step(&rule);
+        assert_eq!(universe.get(0, 0), GridStatus::DYING(1));
+        assert_eq!(universe.get(-1, 0), GridStatus::LIFE);
+        assert_eq!(universe.get(1, 1), GridStatus::LIFE);
+        assert_eq!(universe.population(), 4);
+    }
+}
@@ -0,0 +1,123 @@
+use super::pattern::read_rle;
+use super::{Grid, GridStatus};
+
+// Built-in patterns, as RLE.
+// https://conwaylife.com/wiki/
+const LIBRARY: [(&str, &str); 6] = [
+    ("glider", "x = 3, y = 3\nbo$2bo$3o!"),
+    ("lwss", "x = 5, y = 4\nbo2bo$o4b$o3bo$4o!"),
+    (
+        "pulsar",
+        "x = 13, y = 13\n2b3o3b3o2b2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2b2$\
+         2b3o3b3o2b$o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    ),
+    (
+        "gosper glider gun",
+        "x = 36, y = 9\n24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$\
+         2o8bo3bob2o4bobo$10bo5bo7bo$11bo3bo$12b2o!",
+    ),
+    ("r-pentomino", "x = 3, y = 3\nb2o$2o$bo!"),
+    ("acorn", "x = 7, y = 3\nbo$3bo$2o2b3o!"),
+];
+
+// A pattern picked from the library, turned and mirrored as the user asked,
+// ready to be copied onto the grid around the cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamp {
+    pub name: &'static str,
+    pub cells: Grid,
+}
+
+impl Stamp {
+    pub fn library() -> Vec<Stamp> {
+        LIBRARY
+            .iter()
+            .map(|(name, rle)| Stamp {
+                name,
+                cells: read_rle(rle).unwrap().cells,
+            })
+            .collect()
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    // A quarter turn clockwise.
+    pub fn rotate(&self) -> Stamp {
+        let (width, height) = (self.width(), self.height());
+        let mut cells = vec![vec![GridStatus::NOT; width]; height];
+        for (x, col) in self.cells.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                cells[y][width - 1 - x] = *cell;
+            }
+        }
+        Stamp { cells, ..*self }
+    }
+
+    // Mirrored left to right.
+    pub fn flip(&self) -> Stamp {
+        let cells = self.cells.iter().rev().cloned().collect();
+        Stamp { cells, ..*self }
+    }
+
+    // Live cells with the stamp centred on (x, y), which may fall outside the grid.
+    pub fn cells_at(&self, x: usize, y: usize) -> Vec<(isize, isize)> {
+        let left = x as isize - self.width() as isize / 2;
+        let bottom = y as isize - self.height() as isize / 2;
+        let mut cells = vec![];
+        for (i, col) in self.cells.iter().enumerate() {
+            for (j, cell) in col.iter().enumerate() {
+                if *cell == GridStatus::LIFE {
+                    cells.push((left + i as isize, bottom + j as isize));
+                }
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod test_stamps {
+    use super::*;
+
+    #[test]
+    fn library_patterns() {
+        let populations: Vec<(&str, usize)> = Stamp::library()
+            .iter()
+            .map(|stamp| (stamp.name, stamp.cells_at(0, 0).len()))
+            .collect();
+        assert_eq!(
+            populations,
+            [
+                ("glider", 5),
+                ("lwss", 9),
+                ("pulsar", 48),
+                ("gosper glider gun", 36),
+                ("r-pentomino", 5),
+                ("acorn", 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn rotate_and_flip() {
+        let acorn = Stamp::library().pop().unwrap();
+        let turned = acorn.rotate();
+        assert_eq!((turned.width(), turned.height()), (3, 7));
+        assert_ne!(turned, acorn);
+        assert_eq!(turned.rotate().rotate().rotate(), acorn);
+        assert_ne!(acorn.flip(), acorn);
+        assert_eq!(acorn.flip().flip(), acorn);
+
+        // The glider's top cell turns to the right column, it now heads down and left.
+        let glider = &Stamp::library()[0];
+        let mut cells = glider.rotate().cells_at(1, 1);
+        cells.sort();
+        assert_eq!(cells, [(0, 0), (0, 1), (0, 2), (1, 0), (2, 1)]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+// Samples kept, the oldest are dropped past it. About 8 MB.
+const MAX_SAMPLES: usize = 100_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Sample {
+    pub generation: u64,
+    pub population: u64,
+    // Cells alive now that were not at the previous sample, and the other way round.
+    // Unknown with hashlife, where finding them would take going through every cell.
+    pub births: Option<u64>,
+    pub deaths: Option<u64>,
+    // Live and dying cells, as (min_x, min_y, max_x, max_y).
+    pub bounding_box: Option<(i64, i64, i64, i64)>,
+    // Share of the bounding box that is alive.
+    pub density: f64,
+}
+
+// The latest samples, to compare rules and initial conditions.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    samples: VecDeque<Sample>,
+}
+
+impl Stats {
+    pub fn samples(&self) -> &VecDeque<Sample> {
+        &self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    // `changes` are the births and deaths since the previous sample, when they are known.
+    pub fn record(
+        &mut self,
+        generation: u64,
+        population: u64,
+        changes: Option<(u64, u64)>,
+        bounding_box: Option<(i64, i64, i64, i64)>,
+    ) {
+        let density = match bounding_box {
+            Some((x0, y0, x1, y1)) => {
+                population as f64 / ((x1 - x0 + 1) as f64 * (y1 - y0 + 1) as f64)
+            }
+            None => 0.0,
+        };
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            generation,
+            population,
+            births: changes.map(|(births, _)| births),
+            deaths: changes.map(|(_, deaths)| deaths),
+            bounding_box,
+            density,
+        });
+    }
+
+    // Forgets the samples after `generation`, when the simulation goes back in time.
+    pub fn truncate(&mut self, generation: u64) {
+        self.samples
+            .retain(|sample| sample.generation <= generation);
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            "generation,population,births,deaths,min_x,min_y,max_x,max_y,density\n".to_string();
+        let count = |count: Option<u64>| count.map_or("".to_string(), |count| count.to_string());
+        for sample in self.samples.iter() {
+            let bounds = match sample.bounding_box {
+                Some((x0, y0, x1, y1)) => format!("{x0},{y0},{x1},{y1}"),
+                None => ",,,".to_string(),
+            };
+            writeln!(
+                csv,
+                "{},{},{},{},{bounds},{}",
+                sample.generation,
+                sample.population,
+                count(sample.births),
+                count(sample.deaths),
+                sample.density
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    // An array with an object per sample.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.samples).unwrap()
+    }
+
+    // JSON for a `.json` path, CSV otherwise.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => self.to_json(),
+            _ => self.to_csv(),
+        };
+        std::fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test_stats {
+    use super::*;
+
+    #[test]
+    fn blinker_series() {
+        let mut stats = Stats::default();
+        stats.record(0, 3, Some((0, 0)), Some((5, 4, 5, 6)));
+        stats.record(1, 3, Some((2, 2)), Some((4, 5, 6, 5)));
+        stats.record(2, 0, None, None);
+
+        let sample = &stats.samples()[1];
+        assert_eq!(sample.density, 1.0);
+
+        assert_eq!(
+            stats.to_csv(),
+            "generation,population,births,deaths,min_x,min_y,max_x,max_y,density\n\
+             0,3,0,0,5,4,5,6,1\n\
+             1,3,2,2,4,5,6,5,1\n\
+             2,0,,,,,,,0\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json[1]["bounding_box"], serde_json::json!([4, 5, 6, 5]));
+        assert_eq!(json[2]["births"], serde_json::Value::Null);
+        assert_eq!(json[2]["density"], 0.0);
+
+        stats.truncate(1);
+        assert_eq!(stats.samples().len(), 2);
+    }
+
+    #[test]
+    fn oldest_samples_go() {
+        let mut stats = Stats::default();
+        for generation in 0..MAX_SAMPLES as u64 + 5 {
+            stats.record(generation, 1, Some((0, 0)), Some((0, 0, 0, 0)));
+        }
+        assert_eq!(stats.samples().len(), MAX_SAMPLES);
+        assert_eq!(stats.samples()[0].generation, 5);
+    }
+}
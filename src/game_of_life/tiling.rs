@@ -0,0 +1,180 @@
+// Shape of the cells, set by the rule: B2/S34H is a hexagonal rule, B45/S34T a triangular one.
+//
+// Every tiling keeps the cells in `grid[x][y]`, in world coordinates:
+// - square cells cover [x, x + 1) x [y, y + 1);
+// - hexagons sit in rows, odd rows shifted half a cell to the right,
+//   with 6 neighbours sharing an edge;
+// - triangles alternate pointing up, where x + y is even, and down, each two units wide,
+//   with 12 neighbours sharing an edge or a corner.
+// Wrapping edges keep the pattern only with an even number of rows, and columns for triangles.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Tiling {
+    #[default]
+    Square,
+    Hex,
+    Triangle,
+}
+
+const SQUARE_NEIGHBORS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const HEX_EVEN_ROW_NEIGHBORS: [(isize, isize); 6] =
+    [(-1, 0), (1, 0), (-1, 1), (0, 1), (-1, -1), (0, -1)];
+const HEX_ODD_ROW_NEIGHBORS: [(isize, isize); 6] =
+    [(-1, 0), (1, 0), (0, 1), (1, 1), (0, -1), (1, -1)];
+// Five cells along the flat side, three at the tip.
+const TRIANGLE_UP_NEIGHBORS: [(isize, isize); 12] = [
+    (-2, 0),
+    (-1, 0),
+    (1, 0),
+    (2, 0),
+    (-2, -1),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (2, -1),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const TRIANGLE_DOWN_NEIGHBORS: [(isize, isize); 12] = [
+    (-2, 0),
+    (-1, 0),
+    (1, 0),
+    (2, 0),
+    (-2, 1),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (2, 1),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+impl Tiling {
+    pub fn max_neighbors(&self) -> usize {
+        match self {
+            Tiling::Square => 8,
+            Tiling::Hex => 6,
+            Tiling::Triangle => 12,
+        }
+    }
+
+    // Rule string suffix.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Tiling::Square => "",
+            Tiling::Hex => "H",
+            Tiling::Triangle => "T",
+        }
+    }
+
+    // Offsets from (x, y) to its neighbours.
+    pub fn neighbor_offsets(&self, x: isize, y: isize) -> &'static [(isize, isize)] {
+        match self {
+            Tiling::Square => &SQUARE_NEIGHBORS,
+            Tiling::Hex if y.rem_euclid(2) == 0 => &HEX_EVEN_ROW_NEIGHBORS,
+            Tiling::Hex => &HEX_ODD_ROW_NEIGHBORS,
+            Tiling::Triangle if (x + y).rem_euclid(2) == 0 => &TRIANGLE_UP_NEIGHBORS,
+            Tiling::Triangle => &TRIANGLE_DOWN_NEIGHBORS,
+        }
+    }
+
+    // Corners of the cell in world coordinates, counterclockwise.
+    pub fn vertices(&self, x: i64, y: i64) -> Vec<(f32, f32)> {
+        let points_up = (x + y).rem_euclid(2) == 0;
+        let odd_row = y.rem_euclid(2) == 1;
+        let (x, y) = (x as f32, y as f32);
+        match self {
+            Tiling::Square => vec![(x, y), (x + 1.0, y), (x + 1.0, y + 1.0), (x, y + 1.0)],
+            Tiling::Hex => {
+                let centre_x = x + if odd_row { 1.0 } else { 0.5 };
+                let centre_y = y + 0.5;
+                // Rows are one unit apart, so the hexagons are 4/3 high to interlock.
+                let (third, two_thirds) = (1.0 / 3.0, 2.0 / 3.0);
+                vec![
+                    (centre_x, centre_y + two_thirds),
+                    (centre_x - 0.5, centre_y + third),
+                    (centre_x - 0.5, centre_y - third),
+                    (centre_x, centre_y - two_thirds),
+                    (centre_x + 0.5, centre_y - third),
+                    (centre_x + 0.5, centre_y + third),
+                ]
+            }
+            Tiling::Triangle if points_up => {
+                vec![(x - 0.5, y), (x + 1.5, y), (x + 0.5, y + 1.0)]
+            }
+            Tiling::Triangle => vec![(x - 0.5, y + 1.0), (x + 0.5, y), (x + 1.5, y + 1.0)],
+        }
+    }
+
+    // Cell holding a world point.
+    pub fn cell_at(&self, x: f32, y: f32) -> (i64, i64) {
+        let (floor_x, floor_y) = (x.floor() as i64, y.floor() as i64);
+        if *self == Tiling::Square {
+            return (floor_x, floor_y);
+        }
+        for cell_y in floor_y - 1..=floor_y + 1 {
+            for cell_x in floor_x - 1..=floor_x + 1 {
+                if contains(&self.vertices(cell_x, cell_y), (x, y)) {
+                    return (cell_x, cell_y);
+                }
+            }
+        }
+        (floor_x, floor_y)
+    }
+}
+
+// Whether a convex polygon with counterclockwise corners holds the point.
+fn contains(vertices: &[(f32, f32)], (x, y): (f32, f32)) -> bool {
+    (0..vertices.len()).all(|i| {
+        let (x0, y0) = vertices[i];
+        let (x1, y1) = vertices[(i + 1) % vertices.len()];
+        (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0) >= 0.0
+    })
+}
+
+#[cfg(test)]
+mod test_tiling {
+    use super::*;
+
+    #[test]
+    fn neighbours_are_mutual() {
+        for tiling in [Tiling::Square, Tiling::Hex, Tiling::Triangle] {
+            for (x, y) in [(4, 4), (5, 4), (4, 5), (5, 5)] {
+                let offsets = tiling.neighbor_offsets(x, y);
+                assert_eq!(offsets.len(), tiling.max_neighbors());
+                for (dx, dy) in offsets {
+                    let back = tiling.neighbor_offsets(x + dx, y + dy);
+                    assert!(
+                        back.contains(&(-dx, -dy)),
+                        "{tiling:?} ({x}, {y}) ({dx}, {dy})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn picking_finds_the_cell_drawn() {
+        for tiling in [Tiling::Square, Tiling::Hex, Tiling::Triangle] {
+            for x in 0..4 {
+                for y in 0..4 {
+                    let vertices = tiling.vertices(x, y);
+                    let n = vertices.len() as f32;
+                    let centre_x = vertices.iter().map(|v| v.0).sum::<f32>() / n;
+                    let centre_y = vertices.iter().map(|v| v.1).sum::<f32>() / n;
+                    assert_eq!(tiling.cell_at(centre_x, centre_y), (x, y), "{tiling:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// How the edges of the grid are glued together.
+// https://conwaylife.com/wiki/Bounded_grids
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum Topology {
+    // Cells outside the grid are always dead.
+    #[default]
+    Bounded,
+    // Left meets right and top meets bottom.
+    Torus,
+    // Like the torus, but crossing top/bottom mirrors the column.
+    KleinBottle,
+    // Crossing any edge mirrors the other axis.
+    ProjectivePlane,
+}
+
+impl Topology {
+    // Maps a possibly out of range coordinate back onto a `width` x `height` grid.
+    // `None` means the cell does not exist, it is outside a bounded grid.
+    pub fn wrap(&self, x: isize, y: isize, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (w, h) = (width as isize, height as isize);
+        let crosses_x = x < 0 || x >= w;
+        let crosses_y = y < 0 || y >= h;
+        let (x, y) = match self {
+            Topology::Bounded if crosses_x || crosses_y => return None,
+            Topology::Bounded | Topology::Torus => (x, y),
+            Topology::KleinBottle if crosses_y => (w - 1 - x, y),
+            Topology::KleinBottle => (x, y),
+            Topology::ProjectivePlane => {
+                let x_mirror = if crosses_y { w - 1 - x } else { x };
+                let y_mirror = if crosses_x { h - 1 - y } else { y };
+                (x_mirror, y_mirror)
+            }
+        };
+        Some((x.rem_euclid(w) as usize, y.rem_euclid(h) as usize))
+    }
+
+    // Whether reaching the possibly out of range (x, y) crosses a mirrored edge, which
+    // swaps left and right for what crosses it.
+    pub fn mirrors(&self, x: isize, y: isize, width: usize, height: usize) -> bool {
+        let crosses_x = x < 0 || x >= width as isize;
+        let crosses_y = y < 0 || y >= height as isize;
+        match self {
+            Topology::Bounded | Topology::Torus => false,
+            Topology::KleinBottle => crosses_y,
+            Topology::ProjectivePlane => crosses_x != crosses_y,
+        }
+    }
+}
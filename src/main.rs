@@ -3,11 +3,15 @@ use cli_options::RunOptions;
 
 mod basic_drawings;
 mod cli_options;
+mod elementary;
 mod falling_sand;
 mod game_of_life;
 mod perlin_noise;
 mod rainbow_sinewave;
 mod rgb_linear;
+mod snapshot;
+mod turmite;
+mod wireworld;
 
 fn main() {
     let args = cli_options::Args::parse();
@@ -15,9 +19,12 @@ fn main() {
     match args.name {
         RunOptions::PerlinNoise => perlin_noise::run(),
         RunOptions::AmplitudeNoise => rainbow_sinewave::run(),
-        RunOptions::FallingSand => falling_sand::run(),
+        RunOptions::FallingSand => falling_sand::run(&args),
         RunOptions::RgbLinearTransition => rgb_linear::run(),
-        RunOptions::GameOfLife => game_of_life::run(),
+        RunOptions::GameOfLife => game_of_life::run(&args),
+        RunOptions::Wireworld => wireworld::run(&args),
+        RunOptions::Turmite => turmite::run(&args),
+        RunOptions::Elementary => elementary::run(&args),
         _ => basic_drawings::run(),
     };
 }
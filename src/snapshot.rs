@@ -0,0 +1,16 @@
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Sketch sessions saved as JSON, so a board in progress survives a restart.
+
+pub fn save<T: Serialize>(path: &Path, session: &T) -> Result<(), String> {
+    let json = serde_json::to_string(session).map_err(|e| format!("{}: {e}", path.display()))?;
+    std::fs::write(path, json).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    serde_json::from_str(&json).map_err(|e| format!("{}: {e}", path.display()))
+}
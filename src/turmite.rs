@@ -0,0 +1,679 @@
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use nannou::prelude::*;
+
+use crate::cli_options::Args;
+use crate::game_of_life::{self, zoom_factor, Camera, Clock, Navigation, Topology};
+
+// Turmites: ants walking a grid of coloured cells, turning by the colour they stand on,
+// repainting it and stepping forward. Langton's ant is the turmite `RL`.
+// https://en.wikipedia.org/wiki/Turmite
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = WIDTH;
+// Cells per side of the grid unless `--board-size` asks for more, room for
+// Langton's ant to build its highway.
+const DEFAULT_BOARD_SIZE: usize = 200;
+
+// Steps per second while running.
+const DEFAULT_SPEED: f64 = 200.0;
+const MIN_SPEED: f64 = 1.0;
+const MAX_SPEED: f64 = 65536.0;
+
+// Command line options, read once the window model is built.
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+pub fn run(args: &Args) {
+    if args.headless {
+        return run_headless(args);
+    }
+    ARGS.set(args.clone()).unwrap();
+    nannou::app(model).update(update).run();
+}
+
+// Turns relative to the heading, with their codes in the `{{{...}}}` tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Turn {
+    None,
+    Right,
+    Around,
+    Left,
+}
+
+impl Turn {
+    // Quarter turns clockwise.
+    fn quarters(&self) -> u8 {
+        match self {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::Around => 2,
+            Turn::Left => 3,
+        }
+    }
+
+    fn from_letter(letter: char) -> Option<Turn> {
+        match letter.to_ascii_uppercase() {
+            'N' => Some(Turn::None),
+            'R' => Some(Turn::Right),
+            'U' => Some(Turn::Around),
+            'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    fn from_code(code: u32) -> Option<Turn> {
+        match code {
+            1 => Some(Turn::None),
+            2 => Some(Turn::Right),
+            4 => Some(Turn::Around),
+            8 => Some(Turn::Left),
+            _ => None,
+        }
+    }
+}
+
+// What a turmite in some state does on some colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Action {
+    // Colour left behind on the cell.
+    write: u8,
+    turn: Turn,
+    next_state: usize,
+}
+
+// The rules of the ants, `table[state][colour]`.
+//
+// Either a turn string, a letter per colour: `RL` (Langton's ant), `LLRR`, `RRLLLRLLLRRR`,
+// where N is no turn and U a U-turn. Each colour turns into the next one.
+//
+// Or a 2D turmite table as in Golly and Ed Pegg Jr's notation, a list of states each
+// with a `{write colour, turn, next state}` triple per colour, the turn being 1 (none),
+// 2 (right), 4 (U-turn) or 8 (left). `{{{1,2,0},{0,8,0}}}` is Langton's ant again.
+// https://golly.sourceforge.io/Help/Algorithms/RuleLoader.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct Turmite {
+    table: Vec<Vec<Action>>,
+}
+
+impl Turmite {
+    fn colors(&self) -> usize {
+        self.table[0].len()
+    }
+
+    fn action(&self, state: usize, color: u8) -> Action {
+        self.table[state][color as usize]
+    }
+}
+
+impl Default for Turmite {
+    fn default() -> Self {
+        Turmite::from_str("RL").unwrap()
+    }
+}
+
+impl FromStr for Turmite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if spec.starts_with('{') {
+            return parse_table(&spec);
+        }
+
+        let turns = spec
+            .chars()
+            .map(|letter| {
+                Turn::from_letter(letter)
+                    .ok_or_else(|| format!("invalid turn `{letter}` in turmite `{s}`"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !(2..=u8::MAX as usize).contains(&turns.len()) {
+            return Err(format!(
+                "turmite `{s}` needs between 2 and {} turns",
+                u8::MAX
+            ));
+        }
+        let colors = turns.len();
+        let state = turns
+            .iter()
+            .enumerate()
+            .map(|(color, turn)| Action {
+                write: ((color + 1) % colors) as u8,
+                turn: *turn,
+                next_state: 0,
+            })
+            .collect();
+        Ok(Turmite { table: vec![state] })
+    }
+}
+
+fn parse_table(spec: &str) -> Result<Turmite, String> {
+    let invalid = || format!("invalid turmite table `{spec}`");
+    let body = spec
+        .strip_prefix("{{{")
+        .and_then(|body| body.strip_suffix("}}}"))
+        .ok_or_else(invalid)?;
+    let table = body
+        .split("}},{{")
+        .map(|state| {
+            state
+                .split("},{")
+                .map(|triple| {
+                    let numbers = triple
+                        .split(',')
+                        .map(|n| n.parse::<u32>().map_err(|_| invalid()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match numbers[..] {
+                        [write, turn, next_state] => Ok(Action {
+                            write: u8::try_from(write).map_err(|_| invalid())?,
+                            turn: Turn::from_code(turn).ok_or_else(|| {
+                                format!(
+                                    "invalid turn `{turn}` in turmite table, expected 1, 2, 4 or 8"
+                                )
+                            })?,
+                            next_state: next_state as usize,
+                        }),
+                        _ => Err(invalid()),
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let colors = table[0].len();
+    if table.iter().any(|state| state.len() != colors) {
+        return Err(format!(
+            "every state of turmite table `{spec}` needs the same number of colours"
+        ));
+    }
+    let out_of_range = table
+        .iter()
+        .flatten()
+        .any(|action| action.write as usize >= colors || action.next_state >= table.len());
+    if out_of_range {
+        return Err(format!(
+            "turmite table `{spec}` uses a colour or state it does not have"
+        ));
+    }
+    Ok(Turmite { table })
+}
+
+// Headings, in clockwise quarter turns from north.
+const HEADINGS: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Ant {
+    x: usize,
+    y: usize,
+    // Index in `HEADINGS`.
+    heading: u8,
+    state: usize,
+    // Crossed mirrored edges an odd number of times, so its right is the grid's left.
+    mirrored: bool,
+}
+
+impl Ant {
+    fn new(x: usize, y: usize) -> Self {
+        Ant {
+            x,
+            y,
+            heading: 0,
+            state: 0,
+            mirrored: false,
+        }
+    }
+
+    // Quarter turns clockwise on the grid for a `turn` of the ant.
+    fn quarters(&self, turn: Turn) -> u8 {
+        match self.mirrored {
+            true => (4 - turn.quarters()) % 4,
+            false => turn.quarters(),
+        }
+    }
+}
+
+// Painted cells in each column, or each row, and the first and last holding any.
+#[derive(Clone, Debug, PartialEq)]
+struct Span {
+    counts: Vec<usize>,
+    range: Option<(usize, usize)>,
+}
+
+impl Span {
+    fn new(len: usize) -> Self {
+        Span {
+            counts: vec![0; len],
+            range: None,
+        }
+    }
+
+    fn add(&mut self, i: usize) {
+        self.counts[i] += 1;
+        self.range = Some(match self.range {
+            Some((first, last)) => (first.min(i), last.max(i)),
+            None => (i, i),
+        });
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.counts[i] -= 1;
+        let Some((mut first, mut last)) = self.range else {
+            return;
+        };
+        while first <= last && self.counts[first] == 0 {
+            first += 1;
+        }
+        if first > last {
+            self.range = None;
+            return;
+        }
+        while self.counts[last] == 0 {
+            last -= 1;
+        }
+        self.range = Some((first, last));
+    }
+}
+
+// The grid and the ants on it, without a window.
+#[derive(Clone, Debug, PartialEq)]
+struct World {
+    // Colour of each cell, grid[x][y] with y up.
+    grid: Vec<Vec<u8>>,
+    ants: Vec<Ant>,
+    turmite: Turmite,
+    // Ants walking off a bounded grid stop there, on the other topologies they come back
+    // in on the other side, keeping their heading. Mirrored edges swap their turns.
+    topology: Topology,
+    steps: u64,
+    // Cells not of colour 0, counted as they are painted.
+    painted: usize,
+    columns: Span,
+    rows: Span,
+}
+
+impl World {
+    // A `size` x `size` grid of colour 0 with `ants` ants facing north, spread along a
+    // horizontal line through the middle.
+    fn new(turmite: Turmite, topology: Topology, size: usize, ants: usize) -> Self {
+        let mut world = World {
+            grid: game_of_life::new_grid(0, size, size),
+            ants: vec![],
+            turmite,
+            topology,
+            steps: 0,
+            painted: 0,
+            columns: Span::new(size),
+            rows: Span::new(size),
+        };
+        for i in 0..ants {
+            world.add_ant(size * (2 * i + 1) / (2 * ants), size / 2);
+        }
+        world
+    }
+
+    fn add_ant(&mut self, x: usize, y: usize) {
+        self.ants.push(Ant::new(x, y));
+    }
+
+    fn color(&self, x: usize, y: usize) -> u8 {
+        self.grid[x][y]
+    }
+
+    fn paint(&mut self, x: usize, y: usize, color: u8) {
+        match (self.color(x, y), color) {
+            (0, 0) => {}
+            (0, _) => {
+                self.painted += 1;
+                self.columns.add(x);
+                self.rows.add(y);
+            }
+            (_, 0) => {
+                self.painted -= 1;
+                self.columns.remove(x);
+                self.rows.remove(y);
+            }
+            _ => {}
+        }
+        self.grid[x][y] = color;
+    }
+
+    // Every ant turns by the colour under it, repaints the cell and steps forward,
+    // one ant after the other.
+    fn step(&mut self) {
+        let (width, height) = (self.grid.len(), self.grid[0].len());
+        let mut ants = std::mem::take(&mut self.ants);
+        ants.retain_mut(|ant| {
+            let action = self.turmite.action(ant.state, self.color(ant.x, ant.y));
+            ant.heading = (ant.heading + ant.quarters(action.turn)) % 4;
+            self.paint(ant.x, ant.y, action.write);
+            ant.state = action.next_state;
+
+            let (dx, dy) = HEADINGS[ant.heading as usize];
+            let (x, y) = (ant.x as isize + dx, ant.y as isize + dy);
+            match self.topology.wrap(x, y, width, height) {
+                Some(forward) => {
+                    (ant.x, ant.y) = forward;
+                    ant.mirrored ^= self.topology.mirrors(x, y, width, height);
+                    true
+                }
+                None => false,
+            }
+        });
+        self.ants = ants;
+        self.steps += 1;
+    }
+
+    // Cells not of colour 0, and the box around them.
+    fn painted(&self) -> (usize, Option<(usize, usize, usize, usize)>) {
+        let bounds = match (self.columns.range, self.rows.range) {
+            (Some((x0, x1)), Some((y0, y1))) => Some((x0, y0, x1, y1)),
+            _ => None,
+        };
+        (self.painted, bounds)
+    }
+}
+
+fn world_from_args(args: &Args) -> World {
+    let size = (args.board_size.unwrap_or(0) as usize).max(DEFAULT_BOARD_SIZE);
+    World::new(args.turmite.clone(), args.topology, size, args.ants.max(1))
+}
+
+// Runs the ants without a window, a CSV line per step with the painted cells and the
+// first ant, e.g. to see Langton's ant start its highway after about 10000 steps.
+fn run_headless(args: &Args) {
+    let mut world = world_from_args(args);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    writeln!(out, "step,painted,min_x,min_y,max_x,max_y,ant_x,ant_y").unwrap();
+    write_step(&mut out, &world);
+    for _ in 0..args.generations {
+        world.step();
+        write_step(&mut out, &world);
+    }
+}
+
+fn write_step(out: &mut impl Write, world: &World) {
+    let (painted, bounds) = world.painted();
+    let bounds = match bounds {
+        Some((x0, y0, x1, y1)) => format!("{x0},{y0},{x1},{y1}"),
+        None => ",,,".to_string(),
+    };
+    let ant = match world.ants.first() {
+        Some(ant) => format!("{},{}", ant.x, ant.y),
+        None => ",".to_string(),
+    };
+    writeln!(out, "{},{painted},{bounds},{ant}", world.steps).unwrap();
+}
+
+struct Model {
+    world: World,
+    running: bool,
+    camera: Camera,
+    navigation: Navigation,
+    clock: Clock,
+}
+
+impl Model {
+    fn new(world: World) -> Self {
+        let size = world.grid.len();
+        let middle = size as f32 / 2.0;
+        Model {
+            world,
+            running: false,
+            camera: Camera::new((middle, middle), WIDTH as f32 / size as f32),
+            navigation: Navigation::default(),
+            clock: Clock::new(DEFAULT_SPEED, MIN_SPEED, MAX_SPEED),
+        }
+    }
+
+    // Runs as many steps as the clock asks for in `elapsed` time.
+    fn advance(&mut self, elapsed: Duration) {
+        for _ in 0..self.clock.steps(elapsed) {
+            self.world.step();
+        }
+    }
+}
+
+// Colour 0 is the black background, the others go round the colour wheel.
+fn cell_hue(color: u8, colors: usize) -> f32 {
+    (color as f32 - 1.0) / (colors - 1) as f32 * 0.8
+}
+
+fn model(app: &App) -> Model {
+    app.new_window()
+        .view(view)
+        .size(WIDTH, HEIGHT)
+        .mouse_pressed(mouse_pressed)
+        .mouse_released(mouse_released)
+        .mouse_moved(mouse_moved)
+        .mouse_wheel(mouse_wheel)
+        .key_pressed(key_pressed)
+        .build()
+        .unwrap();
+
+    Model::new(world_from_args(ARGS.get().unwrap()))
+}
+
+fn update(_app: &App, model: &mut Model, update: Update) {
+    if !model.running {
+        return;
+    }
+
+    model.advance(update.since_last);
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    frame.clear(BLACK);
+
+    let draw = app.draw();
+    let background_color = if model.running { BLACK } else { PLUM };
+    draw.background().color(background_color);
+
+    let world = &model.world;
+    let size = world.grid.len() as i64;
+    let colors = world.turmite.colors();
+    let block_size = model.camera.cell_size;
+    let half_block = vec2(block_size, block_size) / 2.0;
+    // The board, so the paused background only shows around it.
+    let board_centre = model
+        .camera
+        .screen_point((size as f32 / 2.0, size as f32 / 2.0));
+    draw.rect()
+        .color(BLACK)
+        .xy(board_centre)
+        .w(size as f32 * block_size)
+        .h(size as f32 * block_size);
+
+    let (x0, y0, x1, y1) = model.camera.visible(WIDTH as f32, HEIGHT as f32);
+    for x in x0.max(0)..=x1.min(size - 1) {
+        for y in y0.max(0)..=y1.min(size - 1) {
+            let color = world.color(x as usize, y as usize);
+            if color == 0 {
+                continue;
+            }
+            draw.rect()
+                .color(hsl(cell_hue(color, colors), 0.7, 0.55))
+                .xy(model.camera.screen_at(x, y) + half_block)
+                .w(block_size)
+                .h(block_size);
+        }
+    }
+    for ant in world.ants.iter() {
+        draw.ellipse()
+            .color(RED)
+            .xy(model.camera.screen_at(ant.x as i64, ant.y as i64) + half_block)
+            .w(block_size.max(4.0))
+            .h(block_size.max(4.0));
+    }
+
+    let hud = format!(
+        "step {}\nants {}\nspeed {} steps/s",
+        world.steps,
+        world.ants.len(),
+        model.clock.speed
+    );
+    draw.text(&hud)
+        .color(WHITE)
+        .font_size(14)
+        .left_justify()
+        .x_y(-(WIDTH as f32) / 2.0 + 100.0, HEIGHT as f32 / 2.0 - 30.0);
+
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    let mouse_point = app.mouse.position();
+    model.navigation.mouse_pressed(button, mouse_point);
+    let size = model.world.grid.len();
+    match button {
+        MouseButton::Right => model.running = !model.running,
+        MouseButton::Left => {
+            if let Some((x, y)) = model.camera.cell_at(mouse_point, size, size) {
+                model.world.add_ant(x, y);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn mouse_released(_app: &App, model: &mut Model, button: MouseButton) {
+    model.navigation.mouse_released(button);
+}
+
+fn mouse_moved(_app: &App, model: &mut Model, position: Point2) {
+    if let Some(delta) = model.navigation.mouse_moved(position) {
+        model.camera.pan(delta);
+    }
+}
+
+fn mouse_wheel(app: &App, model: &mut Model, delta: MouseScrollDelta, _phase: TouchPhase) {
+    model.camera.zoom(zoom_factor(delta), app.mouse.position());
+}
+
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    match key {
+        Key::Space => model.running = !model.running,
+        Key::Right if !model.running => model.world.step(),
+        Key::Up => model.clock.change_speed(true),
+        Key::Down => model.clock.change_speed(false),
+        // Back to the grid and ants of the command line.
+        Key::C => model.world = world_from_args(ARGS.get().unwrap()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test_turmite {
+    use super::*;
+
+    #[test]
+    fn parse_turmites() {
+        let langton: Turmite = "RL".parse().unwrap();
+        assert_eq!(langton, "{{{1, 2, 0}, {0, 8, 0}}}".parse().unwrap());
+        assert_eq!(langton, Turmite::default());
+
+        let long: Turmite = "RRLLLRLLLRRR".parse().unwrap();
+        assert_eq!(long.colors(), 12);
+        assert_eq!(long.action(0, 11).write, 0);
+        assert_eq!(long.action(0, 3).turn, Turn::Left);
+
+        // Fibonacci spiral, two states.
+        let spiral: Turmite = "{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}".parse().unwrap();
+        assert_eq!(spiral.table.len(), 2);
+        assert_eq!(
+            spiral.action(1, 1),
+            Action {
+                write: 0,
+                turn: Turn::None,
+                next_state: 0
+            }
+        );
+
+        assert!("R".parse::<Turmite>().is_err());
+        assert!("RX".parse::<Turmite>().is_err());
+        assert!("{{{1,3,0},{0,8,0}}}".parse::<Turmite>().is_err());
+        assert!("{{{1,2,1},{0,8,0}}}".parse::<Turmite>().is_err());
+        assert!("{{{1,2,0},{0,8,0}},{{1,2,0}}}".parse::<Turmite>().is_err());
+    }
+
+    #[test]
+    fn langtons_ant_builds_a_highway() {
+        let mut world = World::new(Turmite::default(), Topology::Torus, 200, 1);
+        for _ in 0..11_000 {
+            world.step();
+        }
+        // The highway repeats every 104 steps, two cells further along a diagonal.
+        let ant = |world: &World| (world.ants[0].x as isize, world.ants[0].y as isize);
+        let start = ant(&world);
+        let mut moves = vec![];
+        for _ in 0..3 {
+            let before = ant(&world);
+            for _ in 0..104 {
+                world.step();
+            }
+            let after = ant(&world);
+            moves.push((after.0 - before.0, after.1 - before.1));
+        }
+        assert!(moves.iter().all(|m| *m == moves[0]));
+        assert_eq!((moves[0].0.abs(), moves[0].1.abs()), (2, 2));
+        assert_ne!(ant(&world), start);
+    }
+
+    #[test]
+    fn ants_share_the_grid() {
+        let mut world = World::new(Turmite::default(), Topology::Bounded, 10, 2);
+        assert_eq!((world.ants[0].x, world.ants[1].x), (2, 7));
+        world.step();
+        // Both turned right on colour 0 and painted their cell.
+        assert_eq!((world.ants[0].x, world.ants[0].y), (3, 5));
+        assert_eq!((world.ants[1].x, world.ants[1].y), (8, 5));
+        assert_eq!(world.painted(), (2, Some((2, 5, 7, 5))));
+
+        // Walking off a bounded grid stops an ant.
+        world.add_ant(0, 0);
+        world.paint(0, 0, 1);
+        world.step();
+        assert_eq!(world.ants.len(), 2);
+
+        // The box shrinks back as cells go back to colour 0.
+        let mut world = World::new(Turmite::default(), Topology::Bounded, 10, 0);
+        world.paint(1, 1, 1);
+        world.paint(8, 6, 1);
+        world.paint(8, 6, 0);
+        assert_eq!(world.painted(), (1, Some((1, 1, 1, 1))));
+        world.paint(1, 1, 0);
+        assert_eq!(world.painted(), (0, None));
+    }
+
+    #[test]
+    fn mirrored_edges_swap_turns() {
+        let mut world = World::new(Turmite::default(), Topology::KleinBottle, 10, 0);
+        world.ants.push(Ant {
+            heading: 3,
+            ..Ant::new(2, 9)
+        });
+        // A right turn heads north, over the top edge that mirrors the column.
+        world.step();
+        assert_eq!((world.ants[0].x, world.ants[0].y), (7, 0));
+        assert!(world.ants[0].mirrored);
+        // The next right turn is a left one on the grid.
+        world.step();
+        assert_eq!(
+            (world.ants[0].x, world.ants[0].y, world.ants[0].heading),
+            (6, 0, 3)
+        );
+
+        // The torus keeps the turns.
+        let mut world = World::new(Turmite::default(), Topology::Torus, 10, 0);
+        world.ants.push(Ant {
+            heading: 3,
+            ..Ant::new(2, 9)
+        });
+        world.step();
+        world.step();
+        assert_eq!((world.ants[0].x, world.ants[0].y), (3, 0));
+    }
+}
@@ -0,0 +1,411 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use nannou::prelude::*;
+
+use crate::cli_options::Args;
+use crate::game_of_life::{self, zoom_factor, Camera, Clock, Navigation};
+
+// Wireworld: electrons running along wires, enough to build diodes, clocks and logic gates.
+// https://conwaylife.com/wiki/WireWorld
+//
+// - empty cells stay empty;
+// - an electron head becomes an electron tail, and a tail becomes a conductor again;
+// - a conductor becomes an electron head when one or two of its eight neighbours are heads.
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = WIDTH;
+const BLOCK_SIZE: u32 = 10; // width and height in pixels
+
+// Steps per second while running, slow enough to follow the electrons.
+const DEFAULT_SPEED: f64 = 8.0;
+const MIN_SPEED: f64 = 0.5;
+const MAX_SPEED: f64 = 512.0;
+// Palette squares in the top left corner, in pixels.
+const SWATCH_SIZE: f32 = 24.0;
+const SWATCH_MARGIN: f32 = 8.0;
+
+// Command line options, read once the window model is built.
+static ARGS: OnceLock<Args> = OnceLock::new();
+
+pub fn run(args: &Args) {
+    ARGS.set(args.clone()).unwrap();
+    nannou::app(model).update(update).run();
+}
+
+// Numbered as in Golly's WireWorld.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Empty = 0,
+    Head = 1,
+    Tail = 2,
+    Conductor = 3,
+}
+
+// In palette order, also the keys 1 to 4.
+const PALETTE: [Cell; 4] = [Cell::Empty, Cell::Conductor, Cell::Head, Cell::Tail];
+
+// cells[x][y], y up like the window.
+type Cells = Vec<Vec<Cell>>;
+
+impl Cell {
+    fn from_state(state: u8) -> Option<Cell> {
+        match state {
+            0 => Some(Cell::Empty),
+            1 => Some(Cell::Head),
+            2 => Some(Cell::Tail),
+            3 => Some(Cell::Conductor),
+            _ => None,
+        }
+    }
+
+    fn color(&self) -> Rgb8 {
+        match self {
+            Cell::Empty => BLACK,
+            Cell::Conductor => GOLD,
+            Cell::Head => DEEPSKYBLUE,
+            Cell::Tail => CRIMSON,
+        }
+    }
+
+    // The cell's next state, given how many of its eight neighbours are electron heads.
+    fn next(&self, heads: usize) -> Cell {
+        match self {
+            Cell::Empty => Cell::Empty,
+            Cell::Head => Cell::Tail,
+            Cell::Tail => Cell::Conductor,
+            Cell::Conductor if heads == 1 || heads == 2 => Cell::Head,
+            Cell::Conductor => Cell::Conductor,
+        }
+    }
+}
+
+struct Model {
+    grid: Cells,
+    running: bool,
+    generation: u64,
+    // State painted by the left button.
+    brush: Cell,
+    camera: Camera,
+    navigation: Navigation,
+    clock: Clock,
+}
+
+impl Model {
+    // An empty grid with room for the window, or for `size` cells per side if larger.
+    fn new(size: usize) -> Self {
+        let size = size.max((WIDTH / BLOCK_SIZE) as usize);
+        let middle = (size / 2) as f32;
+        Model {
+            grid: game_of_life::new_grid(Cell::Empty, size, size),
+            running: false,
+            generation: 0,
+            brush: Cell::Conductor,
+            camera: Camera::new((middle, middle), BLOCK_SIZE as f32),
+            navigation: Navigation::default(),
+            clock: Clock::new(DEFAULT_SPEED, MIN_SPEED, MAX_SPEED),
+        }
+    }
+
+    // A grid with the `pattern` cells in the middle and a margin of empty cells around.
+    fn with_pattern(pattern: &Cells) -> Self {
+        let (width, height) = (pattern.len(), pattern.first().map_or(0, Vec::len));
+        let mut model = Model::new(width.max(height) + 10);
+        game_of_life::place_cells_centered(&mut model.grid, pattern);
+        model
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Cell {
+        self.grid[x][y]
+    }
+
+    fn step(&mut self) {
+        let (width, height) = (self.grid.len(), self.grid[0].len());
+        let heads = |x: usize, y: usize| {
+            let mut heads = 0;
+            for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                    if (nx, ny) != (x, y) && self.cell(nx, ny) == Cell::Head {
+                        heads += 1;
+                    }
+                }
+            }
+            heads
+        };
+        let next: Cells = (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| self.cell(x, y).next(heads(x, y)))
+                    .collect()
+            })
+            .collect();
+        self.grid = next;
+        self.generation += 1;
+    }
+
+    // Runs as many steps as the clock asks for in `elapsed` time.
+    fn advance(&mut self, elapsed: Duration) {
+        for _ in 0..self.clock.steps(elapsed) {
+            self.step();
+        }
+    }
+
+    fn paint(&mut self, point: Point2) {
+        let (width, height) = (self.grid.len(), self.grid[0].len());
+        if let Some((x, y)) = self.camera.cell_at(point, width, height) {
+            self.grid[x][y] = self.brush;
+        }
+    }
+
+    fn clear(&mut self) {
+        for col in self.grid.iter_mut() {
+            col.fill(Cell::Empty);
+        }
+        self.generation = 0;
+    }
+}
+
+// Window centre of the palette square for `PALETTE[i]`.
+fn swatch_centre(i: usize) -> Point2 {
+    let step = SWATCH_SIZE + SWATCH_MARGIN;
+    pt2(
+        -(WIDTH as f32) / 2.0 + step * (i as f32 + 0.5),
+        HEIGHT as f32 / 2.0 - step / 2.0,
+    )
+}
+
+// Palette entry under a window point.
+fn swatch_at(point: Point2) -> Option<Cell> {
+    (0..PALETTE.len())
+        .find(|i| {
+            let offset = point - swatch_centre(*i);
+            offset.x.abs() <= SWATCH_SIZE / 2.0 && offset.y.abs() <= SWATCH_SIZE / 2.0
+        })
+        .map(|i| PALETTE[i])
+}
+
+// Wireworld patterns in Golly's RLE, where `.` is empty, `A` an electron head,
+// `B` an electron tail and `C` a conductor.
+// https://golly.sourceforge.io/Help/formats.html#rle
+//
+// #C comment lines, including Golly's #CXRLE line
+// x = 5, y = 1, rule = WireWorld
+// BA3C!
+//
+// The rule can be missing, written in any case, or carry a bounded grid suffix like
+// `WireWorld:T60,40`. Plain `b` is read as empty too.
+fn read_rle(text: &str) -> Result<Cells, String> {
+    let (cells, rule) = game_of_life::read_rle_cells(text)?;
+    if let Some(rule) = rule {
+        let name = rule.split(':').next().unwrap_or_default();
+        if !name.eq_ignore_ascii_case("wireworld") {
+            return Err(format!("`{rule}` is not a Wireworld rule"));
+        }
+    }
+    cells
+        .iter()
+        .map(|col| {
+            col.iter()
+                .map(|status| {
+                    let state = status.state();
+                    Cell::from_state(state)
+                        .ok_or_else(|| format!("state {state} is not a Wireworld state"))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn model(app: &App) -> Model {
+    app.new_window()
+        .view(view)
+        .size(WIDTH, HEIGHT)
+        .mouse_pressed(mouse_pressed)
+        .mouse_released(mouse_released)
+        .mouse_moved(mouse_moved)
+        .mouse_wheel(mouse_wheel)
+        .key_pressed(key_pressed)
+        .build()
+        .unwrap();
+
+    match &ARGS.get().unwrap().pattern {
+        Some(path) => {
+            let pattern = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| read_rle(&text))
+                .unwrap_or_else(|e| panic!("Cannot load pattern {}: {e}", path.display()));
+            Model::with_pattern(&pattern)
+        }
+        None => Model::new(0),
+    }
+}
+
+fn update(_app: &App, model: &mut Model, update: Update) {
+    if !model.running {
+        return;
+    }
+
+    model.advance(update.since_last);
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    frame.clear(BLACK);
+
+    let draw = app.draw();
+    let background_color = if model.running { BLACK } else { PLUM };
+    draw.background().color(background_color);
+
+    let block_size = model.camera.cell_size;
+    let (x0, y0, x1, y1) = model.camera.visible(WIDTH as f32, HEIGHT as f32);
+    let (width, height) = (model.grid.len() as i64, model.grid[0].len() as i64);
+    for x in x0.max(0)..=x1.min(width - 1) {
+        for y in y0.max(0)..=y1.min(height - 1) {
+            let centre = model.camera.screen_at(x, y) + vec2(block_size, block_size) / 2.0;
+            draw.rect()
+                .color(model.cell(x as usize, y as usize).color())
+                .stroke(background_color)
+                .stroke_weight(1.0)
+                .xy(centre)
+                .w(block_size)
+                .h(block_size);
+        }
+    }
+
+    // The palette, with the brush outlined.
+    for (i, cell) in PALETTE.iter().enumerate() {
+        let stroke = if *cell == model.brush { WHITE } else { DIMGRAY };
+        draw.rect()
+            .color(cell.color())
+            .stroke(stroke)
+            .stroke_weight(2.0)
+            .xy(swatch_centre(i))
+            .w(SWATCH_SIZE)
+            .h(SWATCH_SIZE);
+    }
+
+    let hud = format!(
+        "generation {}\nspeed {} steps/s\nbrush {:?} (1-4)",
+        model.generation, model.clock.speed, model.brush
+    );
+    draw.text(&hud)
+        .color(WHITE)
+        .font_size(14)
+        .left_justify()
+        .x_y(-(WIDTH as f32) / 2.0 + 100.0, HEIGHT as f32 / 2.0 - 70.0);
+
+    draw.to_frame(app, &frame).unwrap();
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    let mouse_point = app.mouse.position();
+    model.navigation.mouse_pressed(button, mouse_point);
+    match button {
+        MouseButton::Right => model.running = !model.running,
+        MouseButton::Left => match swatch_at(mouse_point) {
+            Some(cell) => model.brush = cell,
+            None => model.paint(mouse_point),
+        },
+        _ => {}
+    }
+}
+
+fn mouse_released(_app: &App, model: &mut Model, button: MouseButton) {
+    model.navigation.mouse_released(button);
+}
+
+// Dragging with the left button paints wires, with the middle button pans.
+fn mouse_moved(app: &App, model: &mut Model, position: Point2) {
+    if let Some(delta) = model.navigation.mouse_moved(position) {
+        model.camera.pan(delta);
+    }
+    if app.mouse.buttons.left().is_down() && swatch_at(position).is_none() {
+        model.paint(position);
+    }
+}
+
+fn mouse_wheel(app: &App, model: &mut Model, delta: MouseScrollDelta, _phase: TouchPhase) {
+    model.camera.zoom(zoom_factor(delta), app.mouse.position());
+}
+
+fn key_pressed(_app: &App, model: &mut Model, key: Key) {
+    match key {
+        Key::Space => model.running = !model.running,
+        Key::Right if !model.running => model.step(),
+        Key::Up => model.clock.change_speed(true),
+        Key::Down => model.clock.change_speed(false),
+        Key::C => model.clear(),
+        Key::Key1 => model.brush = PALETTE[0],
+        Key::Key2 => model.brush = PALETTE[1],
+        Key::Key3 => model.brush = PALETTE[2],
+        Key::Key4 => model.brush = PALETTE[3],
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test_wireworld {
+    use super::*;
+
+    // A wire with an electron heading east, tail first.
+    const WIRE: &str = "#C A wire\nx = 6, y = 1, rule = WireWorld\nBA4C!\n";
+
+    #[test]
+    fn electrons_run_along_wires() {
+        let wire = read_rle(WIRE).unwrap();
+        assert_eq!(wire.len(), 6);
+        assert_eq!(
+            (wire[0][0], wire[1][0], wire[2][0]),
+            (Cell::Tail, Cell::Head, Cell::Conductor)
+        );
+
+        let mut model = Model::with_pattern(&wire);
+        let y = (model.grid.len() - 1) / 2;
+        let start = (model.grid.len() - 6) / 2;
+        model.step();
+        model.step();
+        let row: Vec<Cell> = (start..start + 6).map(|x| model.cell(x, y)).collect();
+        assert_eq!(
+            row,
+            [
+                Cell::Conductor,
+                Cell::Conductor,
+                Cell::Tail,
+                Cell::Head,
+                Cell::Conductor,
+                Cell::Conductor,
+            ]
+        );
+    }
+
+    #[test]
+    fn three_heads_block_a_conductor() {
+        assert_eq!(Cell::Conductor.next(1), Cell::Head);
+        assert_eq!(Cell::Conductor.next(2), Cell::Head);
+        assert_eq!(Cell::Conductor.next(3), Cell::Conductor);
+        assert_eq!(Cell::Empty.next(2), Cell::Empty);
+    }
+
+    #[test]
+    fn rle_variants() {
+        // Golly's extended header, lower case rule with a bounded grid, rows from the top.
+        let text = "#CXRLE Pos=0,0\nx = 2, y = 2, rule = wireworld:T10,10\nCb$.A!";
+        let cells = read_rle(text).unwrap();
+        assert_eq!(cells[0][1], Cell::Conductor);
+        assert_eq!(cells[1][0], Cell::Head);
+        assert_eq!(read_rle("x = 1, y = 1\nC!").unwrap()[0][0], Cell::Conductor);
+
+        assert!(read_rle("x = 1, y = 1, rule = B3/S23\no!").is_err());
+        assert!(read_rle("x = 1, y = 1, rule = WireWorldish\nC!").is_err());
+        assert!(read_rle("x = 1, y = 1\nD!").is_err());
+    }
+
+    #[test]
+    fn palette_picks_the_brush() {
+        assert_eq!(swatch_at(swatch_centre(2)), Some(Cell::Head));
+        assert_eq!(swatch_at(pt2(0.0, 0.0)), None);
+
+        let mut model = Model::new(0);
+        model.brush = Cell::Tail;
+        model.paint(pt2(5.0, 5.0));
+        assert_eq!(model.cell(30, 30), Cell::Tail);
+    }
+}